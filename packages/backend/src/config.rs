@@ -0,0 +1,456 @@
+use chrono::Duration;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::error::{Error, Result};
+
+/// Environment variable with the file the notes are stored in
+pub const FILE_ENV: &str = "VODO_FILE";
+
+/// Environment variable with the notebook that is opened
+pub const NOTEBOOK_ENV: &str = "VODO_NOTEBOOK";
+
+/// Notebook that is opened when none is configured
+pub const DEFAULT_NOTEBOOK: &str = "notes";
+
+/// Number of backups that are kept when none is configured
+pub const DEFAULT_BACKUPS: usize = 3;
+
+/// Extensions of the files the notebooks can be stored in
+const NOTEBOOK_EXTENSIONS: [&str; 2] = ["json", "db"];
+
+/// Lookup of an environment variable, which the tests replace to not depend on the environment
+type Env<'a> = &'a dyn Fn(&str) -> Option<OsString>;
+
+/// User configuration, as read from `$XDG_CONFIG_HOME/vodo/config.toml`
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Where the notes are stored
+    pub storage: StorageConfig,
+
+    /// When notes are archived
+    pub archive: ArchiveConfig,
+
+    /// The `[keys]` section, with the keys of the actions that are bound differently than default
+    pub keys: HashMap<String, Vec<String>>,
+
+    /// Which theme the TUI is shown in
+    pub theme: ThemeConfig,
+
+    /// The `[themes.<name>]` sections, with the themes of the user
+    pub themes: HashMap<String, UserTheme>,
+}
+
+/// The `[theme]` section of the configuration
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct ThemeConfig {
+    /// Name of a built-in theme or a theme of the user, the default depends on `NO_COLOR`
+    pub name: Option<String>,
+
+    /// The `[theme.categories]` section, with the styles of categories like `work = "blue"`
+    pub categories: HashMap<String, String>,
+}
+
+/// A theme of the user, which changes the styles of a built-in theme
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct UserTheme {
+    /// Built-in theme the styles are changed of, dark when it is not set
+    pub base: Option<String>,
+
+    /// Styles of the parts of the TUI, like `todo = "bold blue"`
+    #[serde(flatten)]
+    pub styles: HashMap<String, String>,
+}
+
+/// The `[archive]` section of the configuration
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct ArchiveConfig {
+    /// Number of days after which done notes are archived, never when it is not set
+    pub after_days: Option<u64>,
+}
+
+/// The `[storage]` section of the configuration
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct StorageConfig {
+    /// File to store the notes in, which overrides the notebooks
+    pub file: Option<PathBuf>,
+
+    /// Directory the notebooks are stored in
+    pub data_dir: Option<PathBuf>,
+
+    /// Notebook that is opened by default
+    pub notebook: Option<String>,
+
+    /// Number of backups that are kept of the notes
+    pub backups: Option<usize>,
+
+    /// Format new notebooks are stored in
+    pub format: StorageFormat,
+}
+
+/// Format new notebooks are stored in
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageFormat {
+    /// A JSON file, with backups
+    #[default]
+    Json,
+
+    /// A SQLite database, for large notebooks
+    Sqlite,
+}
+
+impl StorageFormat {
+    /// Extension of the files in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            StorageFormat::Json => "json",
+            StorageFormat::Sqlite => "db",
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration file, a missing file is the default configuration
+    pub fn load() -> Result<Self> {
+        Config::load_with(&process_env)
+    }
+
+    /// Load the configuration file from `$XDG_CONFIG_HOME/vodo` or `$HOME/.config/vodo`,
+    /// with the variables of the environment
+    /// Without either there is no configuration file either, which is the default configuration
+    fn load_with(env: Env) -> Result<Self> {
+        let path = match xdg_dir("XDG_CONFIG_HOME", ".config", env) {
+            Ok(dir) => dir.join("config.toml"),
+            Err(_) => return Ok(Config::default()),
+        };
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        let config: Config = toml::from_str(&content)
+            .map_err(|e| Error::InvalidConfig(format!("{}: {}", path.display(), e)))?;
+        if config.archive.after_days.is_some() && config.archive_after().is_none() {
+            let msg = format!("{}: [archive] after_days is too large", path.display());
+            return Err(Error::InvalidConfig(msg).into());
+        }
+        Ok(config)
+    }
+
+    /// Number of backups that are kept of the notes
+    pub fn backups(&self) -> usize {
+        self.storage.backups.unwrap_or(DEFAULT_BACKUPS)
+    }
+
+    /// Time after which done notes are archived automatically, if they are
+    /// A number of days that is too large is rejected when the configuration is loaded
+    pub fn archive_after(&self) -> Option<Duration> {
+        let days = i64::try_from(self.archive.after_days?).ok()?;
+        Duration::try_days(days)
+    }
+
+    /// Directory the notebooks are stored in
+    /// Defaults to `$XDG_DATA_HOME/vodo`, unless the notes are still in the configuration
+    /// directory where they were stored before notebooks existed
+    pub fn data_dir(&self) -> Result<PathBuf> {
+        self.data_dir_with(&process_env)
+    }
+
+    /// Directory the notebooks are stored in, with the variables of the environment
+    fn data_dir_with(&self, env: Env) -> Result<PathBuf> {
+        if let Some(dir) = &self.storage.data_dir {
+            return Ok(dir.to_owned());
+        }
+        let dir = xdg_dir("XDG_DATA_HOME", ".local/share", env)?;
+        let file = format!("{}.json", DEFAULT_NOTEBOOK);
+        match xdg_dir("XDG_CONFIG_HOME", ".config", env) {
+            Ok(legacy) if !dir.join(&file).exists() && legacy.join(&file).exists() => Ok(legacy),
+            _ => Ok(dir),
+        }
+    }
+
+    /// File a notebook is stored in
+    /// An existing notebook is used in whatever format it is in, a new one gets the configured format
+    pub fn notebook_path(&self, name: &str) -> Result<PathBuf> {
+        self.notebook_path_with(name, &process_env)
+    }
+
+    /// File a notebook is stored in, with the variables of the environment
+    fn notebook_path_with(&self, name: &str, env: Env) -> Result<PathBuf> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(Error::InvalidNotebook.into());
+        }
+        let dir = self.data_dir_with(env)?;
+        let existing = NOTEBOOK_EXTENSIONS
+            .iter()
+            .map(|extension| dir.join(format!("{}.{}", name, extension)))
+            .find(|path| path.exists());
+        Ok(existing
+            .unwrap_or_else(|| dir.join(format!("{}.{}", name, self.storage.format.extension()))))
+    }
+
+    /// Names of all the notebooks in the data directory, sorted
+    pub fn notebooks(&self) -> Result<Vec<String>> {
+        let dir = self.data_dir()?;
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut names: Vec<String> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|e| NOTEBOOK_EXTENSIONS.iter().any(|n| e == *n))
+            })
+            .filter_map(|path| path.file_stem()?.to_str().map(String::from))
+            .collect();
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    /// File the notes are stored in, in order of precedence:
+    /// the given file, the given notebook, `$VODO_FILE`, the configured file,
+    /// `$VODO_NOTEBOOK`, the configured notebook and the default notebook
+    pub fn notes_path(&self, file: Option<PathBuf>, notebook: Option<String>) -> Result<PathBuf> {
+        self.notes_path_with(file, notebook, &process_env)
+    }
+
+    /// File the notes are stored in, with the variables of the environment
+    fn notes_path_with(
+        &self,
+        file: Option<PathBuf>,
+        notebook: Option<String>,
+        env: Env,
+    ) -> Result<PathBuf> {
+        if let Some(file) = file {
+            return Ok(file);
+        }
+        if let Some(notebook) = notebook {
+            return self.notebook_path_with(&notebook, env);
+        }
+        let file = env(FILE_ENV)
+            .map(PathBuf::from)
+            .or_else(|| self.storage.file.to_owned());
+        if let Some(file) = file {
+            return Ok(file);
+        }
+        let notebook = env(NOTEBOOK_ENV)
+            .and_then(|v| v.into_string().ok())
+            .or_else(|| self.storage.notebook.to_owned())
+            .unwrap_or_else(|| DEFAULT_NOTEBOOK.to_owned());
+        self.notebook_path_with(&notebook, env)
+    }
+}
+
+/// Directory of `vodo` inside the XDG directory in the variable, or in the fallback in `$HOME`
+fn xdg_dir(var: &str, fallback: &str, env: Env) -> Result<PathBuf> {
+    let base = match env(var).filter(|v| !v.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env("HOME").ok_or(Error::NoHomeDirectory)?).join(fallback),
+    };
+    Ok(base.join("vodo"))
+}
+
+/// Variable of the environment of the process
+fn process_env(var: &str) -> Option<OsString> {
+    env::var_os(var)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use std::path::Path;
+
+    /// Environment with only the variables
+    fn vars(vars: &[(&str, &Path)]) -> impl Fn(&str) -> Option<OsString> {
+        let vars: HashMap<String, OsString> = vars
+            .iter()
+            .map(|(var, value)| (var.to_string(), value.as_os_str().to_owned()))
+            .collect();
+        move |var| vars.get(var).cloned()
+    }
+
+    /// Configuration with the storage section
+    fn config(file: Option<&str>, notebook: Option<&str>) -> Config {
+        let mut config = Config::default();
+        config.storage.file = file.map(PathBuf::from);
+        config.storage.notebook = notebook.map(String::from);
+        config
+    }
+
+    #[test]
+    fn notes_path_precedence() {
+        let home = TestDir::new();
+        let data = home.join(".local/share/vodo");
+        let env = vars(&[
+            ("HOME", &home),
+            (FILE_ENV, Path::new("env.json")),
+            (NOTEBOOK_ENV, Path::new("env-notebook")),
+        ]);
+        let file = || Some(PathBuf::from("flag.json"));
+        let notebook = || Some(String::from("flag-notebook"));
+        let full = config(Some("config.json"), Some("config-notebook"));
+
+        let path = |config: &Config, file, notebook, env| {
+            config.notes_path_with(file, notebook, env).unwrap()
+        };
+        assert_eq!(
+            path(&full, file(), notebook(), &env),
+            Path::new("flag.json")
+        );
+        assert_eq!(
+            path(&full, None, notebook(), &env),
+            data.join("flag-notebook.json")
+        );
+        assert_eq!(path(&full, None, None, &env), Path::new("env.json"));
+
+        let no_file_env = vars(&[("HOME", &home), (NOTEBOOK_ENV, Path::new("env-notebook"))]);
+        assert_eq!(
+            path(&full, None, None, &no_file_env),
+            Path::new("config.json")
+        );
+
+        let notebooks = config(None, Some("config-notebook"));
+        assert_eq!(
+            path(&notebooks, None, None, &no_file_env),
+            data.join("env-notebook.json")
+        );
+
+        let home_only = vars(&[("HOME", &home)]);
+        assert_eq!(
+            path(&notebooks, None, None, &home_only),
+            data.join("config-notebook.json")
+        );
+        assert_eq!(
+            path(&Config::default(), None, None, &home_only),
+            data.join("notes.json")
+        );
+    }
+
+    #[test]
+    fn notebook_names_must_be_file_names() {
+        let home = TestDir::new();
+        let env = vars(&[("HOME", &home)]);
+        for name in ["", "a/b", "a\\b", ".hidden", ".."] {
+            let result = Config::default().notebook_path_with(name, &env);
+            assert!(result.is_err(), "{:?} is accepted", name);
+        }
+        assert!(Config::default().notebook_path_with("work", &env).is_ok());
+    }
+
+    #[test]
+    fn existing_notebooks_keep_their_format() {
+        let data = TestDir::new();
+        let mut config = Config::default();
+        config.storage.data_dir = Some(data.to_path_buf());
+        config.storage.format = StorageFormat::Sqlite;
+        let env = vars(&[]);
+
+        fs::write(data.join("old.json"), "").unwrap();
+        assert_eq!(
+            config.notebook_path_with("old", &env).unwrap(),
+            data.join("old.json")
+        );
+        assert_eq!(
+            config.notebook_path_with("new", &env).unwrap(),
+            data.join("new.db")
+        );
+    }
+
+    #[test]
+    fn data_dir_falls_back_to_the_legacy_directory() {
+        let home = TestDir::new();
+        let config_home = home.join("config");
+        let data_home = home.join("data");
+        let env = vars(&[
+            ("HOME", &home),
+            ("XDG_CONFIG_HOME", &config_home),
+            ("XDG_DATA_HOME", &data_home),
+        ]);
+        let config = Config::default();
+        assert_eq!(config.data_dir_with(&env).unwrap(), data_home.join("vodo"));
+
+        // notes that are only in the configuration directory are still found there
+        fs::create_dir_all(config_home.join("vodo")).unwrap();
+        fs::write(config_home.join("vodo/notes.json"), "").unwrap();
+        assert_eq!(
+            config.data_dir_with(&env).unwrap(),
+            config_home.join("vodo")
+        );
+
+        // once notes exist in the data directory it is used
+        fs::create_dir_all(data_home.join("vodo")).unwrap();
+        fs::write(data_home.join("vodo/notes.json"), "").unwrap();
+        assert_eq!(config.data_dir_with(&env).unwrap(), data_home.join("vodo"));
+    }
+
+    #[test]
+    fn data_dir_needs_a_home() {
+        assert!(Config::default().data_dir_with(&vars(&[])).is_err());
+        // unless the notebooks are not in it
+        let data_home = TestDir::new();
+        let env = vars(&[("XDG_DATA_HOME", &data_home)]);
+        assert_eq!(
+            Config::default().data_dir_with(&env).unwrap(),
+            data_home.join("vodo")
+        );
+    }
+
+    #[test]
+    fn files_do_not_need_a_home() {
+        let env = vars(&[]);
+        let config = Config::load_with(&env).unwrap();
+        assert_eq!(
+            config
+                .notes_path_with(Some(PathBuf::from("flag.json")), None, &env)
+                .unwrap(),
+            Path::new("flag.json")
+        );
+        assert!(config.notes_path_with(None, None, &env).is_err());
+
+        let env = vars(&[(FILE_ENV, Path::new("env.json"))]);
+        assert_eq!(
+            config.notes_path_with(None, None, &env).unwrap(),
+            Path::new("env.json")
+        );
+    }
+
+    #[test]
+    fn load_reads_the_configuration_directory() {
+        let config_home = TestDir::new();
+        let env = vars(&[("XDG_CONFIG_HOME", &config_home)]);
+        assert!(Config::load_with(&env).unwrap().storage.file.is_none());
+
+        fs::create_dir_all(config_home.join("vodo")).unwrap();
+        let path = config_home.join("vodo/config.toml");
+        fs::write(&path, "[storage]\nfile = \"notes.json\"\n").unwrap();
+        let config = Config::load_with(&env).unwrap();
+        assert_eq!(config.storage.file, Some(PathBuf::from("notes.json")));
+
+        fs::write(&path, "[storage\n").unwrap();
+        assert!(Config::load_with(&env).is_err());
+
+        fs::write(&path, "[archive]\nafter_days = 30\n").unwrap();
+        let config = Config::load_with(&env).unwrap();
+        assert_eq!(config.archive_after(), Some(Duration::days(30)));
+        for days in [u64::MAX, i64::MAX as u64] {
+            fs::write(&path, format!("[archive]\nafter_days = {}\n", days)).unwrap();
+            let error = Config::load_with(&env).unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<Error>(),
+                Some(Error::InvalidConfig(_))
+            ));
+        }
+    }
+}
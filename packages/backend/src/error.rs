@@ -1,4 +1,5 @@
 use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
 
 /// User-level errors that can be thrown at runtime
 #[derive(Debug)]
@@ -7,6 +8,38 @@ pub enum Error {
     UnableToCreateFile,
     /// Unable to write the output of the note to the file
     UnableToSaveFile,
+    /// The state does not exist
+    UnknownState,
+    /// The date is not in the `YYYY-MM-DD` format
+    InvalidDate,
+    /// There is no note with the id
+    NoteNotFound,
+    /// The id matches more than one note
+    AmbiguousId,
+    /// `$HOME` is not set, so the default locations can not be found
+    NoHomeDirectory,
+    /// The configuration file could not be parsed
+    InvalidConfig(String),
+    /// The name of the notebook can not be used as a file name
+    InvalidNotebook,
+    /// There is no backup with that number
+    BackupNotFound,
+    /// A note can not be moved under itself or one of its children
+    InvalidParent,
+    /// The storage is not supported by this build
+    UnsupportedStorage,
+    /// The notes are stored in a newer version of the format than this version supports
+    UnsupportedVersion(u64),
+    /// The stored notes could not be parsed, the file is moved aside and a backup is restored
+    /// when there is one
+    CorruptStorage {
+        /// File that could not be parsed
+        path: PathBuf,
+        /// Where the file was moved to
+        quarantined: PathBuf,
+        /// Number of the backup that was restored
+        backup: Option<usize>,
+    },
 }
 
 impl std::error::Error for Error {}
@@ -19,6 +52,45 @@ impl Display for Error {
         match self {
             Error::UnableToCreateFile => write!(f, "Unable to create file"),
             Error::UnableToSaveFile => write!(f, "Unable to save file"),
+            Error::UnknownState => write!(
+                f,
+                "Unknown state, expected one of: none, todo, in-progress, done, expired"
+            ),
+            Error::InvalidDate => write!(f, "Invalid date, expected YYYY-MM-DD"),
+            Error::NoteNotFound => write!(f, "No note with that id"),
+            Error::AmbiguousId => write!(f, "More than one note matches that id"),
+            Error::NoHomeDirectory => write!(f, "Unable to find the home directory"),
+            Error::InvalidConfig(e) => write!(f, "Invalid configuration in {}", e),
+            Error::InvalidNotebook => write!(f, "Invalid notebook name"),
+            Error::BackupNotFound => write!(f, "No backup with that number"),
+            Error::InvalidParent => write!(
+                f,
+                "A note can not be moved under itself or one of its children"
+            ),
+            Error::UnsupportedStorage => {
+                write!(f, "This storage is not supported by this build of vodo")
+            }
+            Error::UnsupportedVersion(version) => write!(
+                f,
+                "The notes are stored in version {} of the format, which requires a newer version of vodo",
+                version
+            ),
+            Error::CorruptStorage {
+                path,
+                quarantined,
+                backup,
+            } => {
+                write!(
+                    f,
+                    "Unable to read the notes in {}, the file was moved to {}",
+                    path.display(),
+                    quarantined.display()
+                )?;
+                match backup {
+                    Some(n) => write!(f, " and backup {} was restored", n),
+                    None => write!(f, " and there was no backup to restore"),
+                }
+            }
         }
     }
 }
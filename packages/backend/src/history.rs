@@ -0,0 +1,322 @@
+use crate::note::Note;
+
+/// Number of commands that can be undone
+pub const HISTORY_LIMIT: usize = 100;
+
+/// A reversible change to the notes
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// A note was added at an index
+    Put {
+        /// Index the note was added at
+        idx: usize,
+
+        /// The added note
+        note: Note,
+    },
+
+    /// A note was deleted from an index
+    Delete {
+        /// Index the note was deleted from
+        idx: usize,
+
+        /// The deleted note
+        note: Note,
+    },
+
+    /// A note was replaced by a newer version of itself
+    Update {
+        /// The note before the update
+        before: Box<Note>,
+
+        /// The note after the update
+        after: Box<Note>,
+    },
+
+    /// A note was moved to another index
+    Move {
+        /// Index the note was moved from
+        from: usize,
+
+        /// Index the note was moved to
+        to: usize,
+    },
+
+    /// Two notes swapped places
+    Swap {
+        /// Index of one of the notes
+        a: usize,
+
+        /// Index of the other note
+        b: usize,
+    },
+
+    /// All the notes were replaced, e.g. by a backup
+    Replace {
+        /// The notes before they were replaced
+        before: Vec<Note>,
+
+        /// The notes after they were replaced
+        after: Vec<Note>,
+    },
+
+    /// Several commands that are undone and redone as one
+    Batch(Vec<Command>),
+}
+
+impl Command {
+    /// The command that reverts this command
+    pub fn inverse(&self) -> Command {
+        match self.to_owned() {
+            Command::Put { idx, note } => Command::Delete { idx, note },
+            Command::Delete { idx, note } => Command::Put { idx, note },
+            Command::Update { before, after } => Command::Update {
+                before: after,
+                after: before,
+            },
+            Command::Move { from, to } => Command::Move { from: to, to: from },
+            Command::Swap { a, b } => Command::Swap { a, b },
+            Command::Replace { before, after } => Command::Replace {
+                before: after,
+                after: before,
+            },
+            Command::Batch(commands) => {
+                Command::Batch(commands.iter().rev().map(Command::inverse).collect())
+            }
+        }
+    }
+
+    /// Apply the command to the notes
+    /// Notes are looked up by id where possible, so commands still apply when other notes have
+    /// changed in the meantime, and indexes that are out of range are clamped
+    pub fn apply(&self, notes: &mut Vec<Note>) {
+        match self {
+            Command::Put { idx, note } => notes.insert((*idx).min(notes.len()), note.to_owned()),
+            Command::Delete { note, .. } => notes.retain(|n| n.id != note.id),
+            Command::Update { after, .. } => {
+                if let Some(n) = notes.iter_mut().find(|n| n.id == after.id) {
+                    *n = *after.to_owned();
+                }
+            }
+            Command::Move { from, to } => {
+                if *from < notes.len() {
+                    let note = notes.remove(*from);
+                    notes.insert((*to).min(notes.len()), note);
+                }
+            }
+            Command::Swap { a, b } => {
+                if *a < notes.len() && *b < notes.len() {
+                    notes.swap(*a, *b);
+                }
+            }
+            Command::Replace { after, .. } => *notes = after.to_owned(),
+            Command::Batch(commands) => {
+                for command in commands {
+                    command.apply(notes);
+                }
+            }
+        }
+    }
+}
+
+/// Commands that can be undone and redone
+#[derive(Debug, Default)]
+pub struct History {
+    /// Commands that can be undone, the most recent last
+    undo: Vec<Command>,
+
+    /// Commands that were undone and can be redone, the most recently undone last
+    redo: Vec<Command>,
+}
+
+impl History {
+    /// Record a command that was just applied, which can no longer be redone after another one
+    pub fn record(&mut self, command: Command) {
+        self.redo.clear();
+        self.undo.push(command);
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+    }
+
+    /// Add a command that was just applied on its own, like expiring overdue notes, to the most
+    /// recent command, so they are undone together
+    /// On its own it would apply again right after it is undone, which would keep the commands
+    /// before it from ever being undone
+    pub fn amend(&mut self, command: Command) {
+        if let Some(last) = self.undo.pop() {
+            self.undo.push(Command::Batch(vec![last, command]));
+        }
+    }
+
+    /// Revert the most recent command on the notes, returns whether there was one
+    pub fn undo(&mut self, notes: &mut Vec<Note>) -> bool {
+        match self.undo.pop() {
+            Some(command) => {
+                command.inverse().apply(notes);
+                self.redo.push(command);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Apply the most recently undone command to the notes again, returns whether there was one
+    pub fn redo(&mut self, notes: &mut Vec<Note>) -> bool {
+        match self.redo.pop() {
+            Some(command) => {
+                command.apply(notes);
+                self.undo.push(command);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether there is a command to undo
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Whether there is a command to redo
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::State;
+
+    /// Notes with the titles
+    fn notes(titles: &[&str]) -> Vec<Note> {
+        titles
+            .iter()
+            .map(|title| Note::new(*title, String::new(), State::Todo))
+            .collect()
+    }
+
+    /// Titles of the notes, in order
+    fn titles(notes: &[Note]) -> Vec<&str> {
+        notes.iter().map(|n| n.title.as_str()).collect()
+    }
+
+    /// Apply and record the command, like `Notes` does
+    fn run(history: &mut History, notes: &mut Vec<Note>, command: Command) {
+        command.apply(notes);
+        history.record(command);
+    }
+
+    #[test]
+    fn undo_and_redo_every_command() {
+        let mut map = notes(&["a", "b", "c"]);
+        let original = map.to_owned();
+        let mut updated = map[1].to_owned();
+        updated.title = String::from("B");
+        let commands = vec![
+            Command::Put {
+                idx: 1,
+                note: Note::new("d", String::new(), State::Todo),
+            },
+            Command::Delete {
+                idx: 0,
+                note: map[0].to_owned(),
+            },
+            Command::Update {
+                before: Box::new(map[1].to_owned()),
+                after: Box::new(updated),
+            },
+            Command::Move { from: 0, to: 2 },
+            Command::Swap { a: 0, b: 2 },
+            Command::Replace {
+                before: map.to_owned(),
+                after: notes(&["x"]),
+            },
+            Command::Batch(vec![
+                Command::Delete {
+                    idx: 2,
+                    note: map[2].to_owned(),
+                },
+                Command::Delete {
+                    idx: 0,
+                    note: map[0].to_owned(),
+                },
+            ]),
+        ];
+        for command in commands {
+            let mut history = History::default();
+            run(&mut history, &mut map, command);
+            let changed = map.to_owned();
+            assert!(history.undo(&mut map));
+            assert_eq!(map, original);
+            assert!(history.redo(&mut map));
+            assert_eq!(map, changed);
+            assert!(history.undo(&mut map));
+        }
+    }
+
+    #[test]
+    fn undo_in_order() {
+        let mut map = notes(&["a", "b"]);
+        let mut history = History::default();
+        run(&mut history, &mut map, Command::Move { from: 0, to: 1 });
+        let note = map[0].to_owned();
+        run(&mut history, &mut map, Command::Delete { idx: 0, note });
+        assert_eq!(titles(&map), ["a"]);
+        history.undo(&mut map);
+        assert_eq!(titles(&map), ["b", "a"]);
+        history.undo(&mut map);
+        assert_eq!(titles(&map), ["a", "b"]);
+        assert!(!history.undo(&mut map));
+    }
+
+    #[test]
+    fn record_clears_redo() {
+        let mut map = notes(&["a", "b"]);
+        let mut history = History::default();
+        run(&mut history, &mut map, Command::Swap { a: 0, b: 1 });
+        history.undo(&mut map);
+        assert!(history.can_redo());
+        run(&mut history, &mut map, Command::Move { from: 1, to: 0 });
+        assert!(!history.can_redo());
+        assert!(!history.redo(&mut map));
+    }
+
+    #[test]
+    fn amend_is_undone_with_the_most_recent_command() {
+        let mut map = notes(&["a", "b", "c"]);
+        let mut history = History::default();
+        // without an earlier command the amended one can not be undone
+        Command::Swap { a: 1, b: 2 }.apply(&mut map);
+        history.amend(Command::Swap { a: 1, b: 2 });
+        assert!(!history.can_undo());
+
+        run(&mut history, &mut map, Command::Move { from: 0, to: 2 });
+        run(&mut history, &mut map, Command::Swap { a: 0, b: 1 });
+        history.undo(&mut map);
+        assert_eq!(titles(&map), ["c", "b", "a"]);
+        Command::Swap { a: 0, b: 1 }.apply(&mut map);
+        history.amend(Command::Swap { a: 0, b: 1 });
+        // the amended command does not prevent a redo
+        assert!(history.can_redo());
+
+        assert!(history.undo(&mut map));
+        assert_eq!(titles(&map), ["a", "c", "b"]);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn history_is_limited() {
+        let mut map = notes(&["a", "b"]);
+        let mut history = History::default();
+        for _ in 0..HISTORY_LIMIT + 10 {
+            run(&mut history, &mut map, Command::Swap { a: 0, b: 1 });
+        }
+        let mut undone = 0;
+        while history.undo(&mut map) {
+            undone += 1;
+        }
+        assert_eq!(undone, HISTORY_LIMIT);
+    }
+}
@@ -7,8 +7,30 @@
     clippy::cargo
 )]
 
+/// Configuration of the storage location
+pub mod config;
+
 /// Error module
-mod error;
+pub mod error;
+
+/// Undo and redo of changes to the notes
+pub mod history;
+
+/// Upgrades of the storage format
+mod migration;
 
 /// Data structure model
 pub mod note;
+
+/// Narrowing down the notes
+pub mod query;
+
+/// Fuzzy searching of notes
+pub mod search;
+
+/// Where the notes are stored
+pub mod storage;
+
+/// Temporary directories for the tests
+#[cfg(test)]
+mod test_dir;
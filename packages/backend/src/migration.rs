@@ -0,0 +1,234 @@
+use serde::de::Error as _;
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+
+/// Version of the storage format that is written
+pub const CURRENT_VERSION: u64 = 5;
+
+/// A single step, which upgrades the stored notes from one version to the next
+type Migration = fn(Value) -> Result<Value>;
+
+/// All the migrations, where the migration at index `n` upgrades version `n` to `n + 1`
+const MIGRATIONS: [Migration; CURRENT_VERSION as usize] =
+    [v0_to_v1, v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5];
+
+/// Upgrade the stored notes, of any older version, step by step to the current version
+/// Returns the upgraded notes and the version they were stored in
+pub fn migrate(mut value: Value) -> Result<(Value, u64)> {
+    let from = version(&value)?;
+    if from > CURRENT_VERSION {
+        return Err(Error::UnsupportedVersion(from).into());
+    }
+    for migration in &MIGRATIONS[from as usize..] {
+        value = migration(value)?;
+    }
+    Ok((value, from))
+}
+
+/// Version of the stored notes, a bare list of notes predates versioning and is version 0
+fn version(value: &Value) -> Result<u64> {
+    match value {
+        Value::Array(_) => Ok(0),
+        Value::Object(object) => object
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| invalid("missing version")),
+        _ => Err(invalid("expected a list of notes or an object")),
+    }
+}
+
+/// Notes of a stored envelope
+fn notes_mut(value: &mut Value) -> Result<&mut Vec<Value>> {
+    value
+        .get_mut("notes")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| invalid("missing notes"))
+}
+
+/// Error for stored notes that do not have the structure of their version
+/// This is a JSON error, so the file is treated like any other file that can not be parsed
+fn invalid(msg: &str) -> Box<dyn std::error::Error> {
+    Box::new(serde_json::Error::custom(msg))
+}
+
+/// Wrap the bare list of notes in an envelope with a version
+fn v0_to_v1(value: Value) -> Result<Value> {
+    Ok(json!({
+        "version": 1,
+        "notes": value,
+    }))
+}
+
+/// Give every note an id and store the fields that were added with their defaults
+fn v1_to_v2(mut value: Value) -> Result<Value> {
+    for note in notes_mut(&mut value)? {
+        let note: &mut Map<String, Value> = note
+            .as_object_mut()
+            .ok_or_else(|| invalid("expected a note"))?;
+        let has_id = note
+            .get("id")
+            .and_then(Value::as_str)
+            .and_then(|id| Uuid::parse_str(id).ok())
+            .is_some_and(|id| !id.is_nil());
+        if !has_id {
+            note.insert(String::from("id"), json!(Uuid::new_v4()));
+        }
+        note.entry("ends_at").or_insert(Value::Null);
+        note.entry("tags").or_insert_with(|| json!([]));
+        note.entry("description").or_insert(Value::Null);
+    }
+    value["version"] = json!(2);
+    Ok(value)
+}
+
+/// Store that none of the notes are archived yet
+fn v2_to_v3(mut value: Value) -> Result<Value> {
+    for note in notes_mut(&mut value)? {
+        let note: &mut Map<String, Value> = note
+            .as_object_mut()
+            .ok_or_else(|| invalid("expected a note"))?;
+        note.entry("archived_at").or_insert(Value::Null);
+    }
+    value["version"] = json!(3);
+    Ok(value)
+}
+
+/// Give every note an empty checklist
+fn v3_to_v4(mut value: Value) -> Result<Value> {
+    for note in notes_mut(&mut value)? {
+        let note: &mut Map<String, Value> = note
+            .as_object_mut()
+            .ok_or_else(|| invalid("expected a note"))?;
+        note.entry("checklist").or_insert_with(|| json!([]));
+    }
+    value["version"] = json!(4);
+    Ok(value)
+}
+
+/// Store that none of the notes have a parent yet
+fn v4_to_v5(mut value: Value) -> Result<Value> {
+    for note in notes_mut(&mut value)? {
+        let note: &mut Map<String, Value> = note
+            .as_object_mut()
+            .ok_or_else(|| invalid("expected a note"))?;
+        note.entry("parent").or_insert(Value::Null);
+    }
+    value["version"] = json!(5);
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A note as it was stored before versioning
+    fn v0_note() -> Value {
+        json!({
+            "title": "title",
+            "state": "Todo",
+            "category": "category",
+            "created_at": "2022-06-01T12:00:00+00:00",
+            "updated_at": "2022-06-01T12:00:00+00:00",
+        })
+    }
+
+    #[test]
+    fn v0_to_v1_wraps_notes() {
+        let value = v0_to_v1(json!([v0_note()])).unwrap();
+        assert_eq!(value, json!({ "version": 1, "notes": [v0_note()] }));
+    }
+
+    #[test]
+    fn v1_to_v2_assigns_ids_and_defaults() {
+        let value = v1_to_v2(json!({ "version": 1, "notes": [v0_note()] })).unwrap();
+        let note = &value["notes"][0];
+        assert_eq!(value["version"], 2);
+        assert!(Uuid::parse_str(note["id"].as_str().unwrap()).is_ok());
+        assert_eq!(note["ends_at"], Value::Null);
+        assert_eq!(note["tags"], json!([]));
+        assert_eq!(note["description"], Value::Null);
+        assert_eq!(note["title"], "title");
+    }
+
+    #[test]
+    fn v1_to_v2_keeps_existing_fields() {
+        let id = Uuid::new_v4();
+        let mut note = v0_note();
+        note["id"] = json!(id);
+        note["tags"] = json!(["tag"]);
+        let value = v1_to_v2(json!({ "version": 1, "notes": [note] })).unwrap();
+        assert_eq!(value["notes"][0]["id"], json!(id));
+        assert_eq!(value["notes"][0]["tags"], json!(["tag"]));
+    }
+
+    #[test]
+    fn v1_to_v2_replaces_nil_ids() {
+        let mut note = v0_note();
+        note["id"] = json!(Uuid::nil());
+        let value = v1_to_v2(json!({ "version": 1, "notes": [note] })).unwrap();
+        assert_ne!(value["notes"][0]["id"], json!(Uuid::nil()));
+    }
+
+    #[test]
+    fn v2_to_v3_adds_archived_at() {
+        let v2 = v1_to_v2(json!({ "version": 1, "notes": [v0_note()] })).unwrap();
+        let value = v2_to_v3(v2).unwrap();
+        assert_eq!(value["version"], 3);
+        assert_eq!(value["notes"][0]["archived_at"], Value::Null);
+    }
+
+    #[test]
+    fn v3_to_v4_adds_checklist() {
+        let v3 = json!({ "version": 3, "notes": [v0_note()] });
+        let value = v3_to_v4(v3).unwrap();
+        assert_eq!(value["version"], 4);
+        assert_eq!(value["notes"][0]["checklist"], json!([]));
+    }
+
+    #[test]
+    fn v4_to_v5_adds_parent() {
+        let v4 = json!({ "version": 4, "notes": [v0_note()] });
+        let value = v4_to_v5(v4).unwrap();
+        assert_eq!(value["version"], 5);
+        assert_eq!(value["notes"][0]["parent"], Value::Null);
+    }
+
+    #[test]
+    fn migrate_bare_list_to_current() {
+        let (value, from) = migrate(json!([v0_note(), v0_note()])).unwrap();
+        assert_eq!(from, 0);
+        assert_eq!(value["version"], CURRENT_VERSION);
+        assert_eq!(value["notes"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn migrate_current_is_unchanged() {
+        let current = migrate(json!([v0_note()])).unwrap().0;
+        let (value, from) = migrate(current.clone()).unwrap();
+        assert_eq!(from, CURRENT_VERSION);
+        assert_eq!(value, current);
+    }
+
+    #[test]
+    fn migrate_newer_version_fails() {
+        let newer = json!({ "version": CURRENT_VERSION + 1, "notes": [] });
+        let e = migrate(newer).unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<Error>(),
+            Some(Error::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn migrate_invalid_structure_is_a_json_error() {
+        for value in [
+            json!("notes"),
+            json!({ "notes": [] }),
+            json!({ "version": 1 }),
+        ] {
+            assert!(migrate(value).unwrap_err().is::<serde_json::Error>());
+        }
+    }
+}
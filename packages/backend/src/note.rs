@@ -1,16 +1,20 @@
-use chrono::Utc;
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
-use serde_json;
-use std::env;
-use std::fs;
-use std::io::BufReader;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::str::FromStr;
+use uuid::Uuid;
 
+use crate::config::Config;
 use crate::error::{Error, Result};
+use crate::history::{Command, History};
+use crate::query::Query;
+use crate::search::fuzzy_match;
+use crate::storage::{self, Storage};
 
 /// State of a note
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// States are ordered from not started to finished
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum State {
     /// No state
     None,
@@ -42,72 +46,158 @@ impl From<State> for String {
     }
 }
 
+impl FromStr for State {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().replace(['-', '_', ' '], "").as_str() {
+            "none" => Ok(State::None),
+            "todo" => Ok(State::Todo),
+            "inprogress" => Ok(State::InProgress),
+            "done" => Ok(State::Done),
+            "expired" => Ok(State::Expired),
+            _ => Err(Error::UnknownState),
+        }
+    }
+}
+
+/// How a set of tags has to match the tags of a note
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TagMatch {
+    /// At least one of the tags is on the note
+    #[default]
+    Any,
+
+    /// All of the tags are on the note
+    All,
+}
+
+/// A note as a row of the tree of notes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TreeRow {
+    /// Index of the note
+    pub idx: usize,
+
+    /// Number of ancestors of the note in the tree
+    pub depth: usize,
+
+    /// Whether the note has children in the tree, even when they are collapsed
+    pub has_children: bool,
+}
+
 /// List of all your notes
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Debug)]
 pub struct Notes {
     /// The actual notes
     pub map: Vec<Note>,
 
-    /// File path of the notes
-    path: PathBuf,
-}
+    /// Where the notes are stored
+    storage: Box<dyn Storage>,
 
-impl Default for Notes {
-    fn default() -> Self {
-        let mut home = env::var("HOME").unwrap();
-        home.push_str("/.config/vodo/notes.json");
-        Self {
-            map: Default::default(),
-            path: PathBuf::from_str(&home).unwrap(),
-        }
-    }
+    /// Why the notes had to be recovered while loading, if they had to be
+    pub recovery: Option<Error>,
+
+    /// Changes to the notes that can be undone
+    history: History,
 }
 
 impl Notes {
-    /// Load new lotes
+    /// Load the notes from the default location
     pub fn new() -> Result<Self> {
-        Notes::load_storage(&Notes::default())
+        let config = Config::load()?;
+        Notes::open(config.notes_path(None, None)?, &config)
+    }
+
+    /// Load the notes from a file, the kind of storage depends on its extension
+    /// Finished notes are archived when that is configured
+    pub fn open(path: impl Into<PathBuf>, config: &Config) -> Result<Self> {
+        let mut notes = Notes::with_storage(storage::open(path, config)?)?;
+        if let Some(after) = config.archive_after() {
+            notes.archive_finished(after)?;
+        }
+        Ok(notes)
     }
 
     /// Load the notes from storage
-    pub fn load_storage(&self) -> Result<Notes> {
-        if !self.path.exists() {
-            // Get the directories
-            let prefix = self.path.parent().unwrap();
+    pub fn with_storage(mut storage: Box<dyn Storage>) -> Result<Self> {
+        let map = storage.load()?;
+        let mut notes = Notes {
+            map,
+            recovery: storage.take_recovery(),
+            storage,
+            history: History::default(),
+        };
+        notes.expire_overdue()?;
+        Ok(notes)
+    }
 
-            // create all the required directories
-            fs::create_dir_all(prefix)?;
+    /// Name of the notebook
+    pub fn notebook(&self) -> String {
+        self.storage.name()
+    }
 
-            // Create the configuration file
-            fs::File::create(&self.path)?;
+    /// Save the notes to storage
+    pub fn save(&mut self) -> Result<()> {
+        self.storage.save(&self.map)
+    }
+
+    /// Apply a change to the notes, so it can be undone, and save them
+    fn run(&mut self, command: Command) -> Result<()> {
+        command.apply(&mut self.map);
+        self.history.record(command);
+        self.save()
+    }
+
+    /// Apply a change that is made on its own, like expiring overdue notes, and save it
+    /// It is undone together with the most recent change
+    fn run_amend(&mut self, command: Command) -> Result<()> {
+        command.apply(&mut self.map);
+        self.history.amend(command);
+        self.save()
+    }
+
+    /// Undo the most recent change, returns whether there was one
+    pub fn undo(&mut self) -> Result<bool> {
+        let undone = self.history.undo(&mut self.map);
+        if undone {
+            self.save()?;
         }
-        let file = fs::File::open(&self.path)?;
-        let reader = BufReader::new(file);
-        let map: Vec<Note> = serde_json::from_reader(reader).unwrap_or_default();
-        Ok(Notes {
-            map,
-            path: self.path.to_owned(),
-        })
+        Ok(undone)
     }
 
-    /// Save the notes to storage
-    pub fn save(&self) -> Result<()> {
-        let file = &fs::File::create(&self.path)
-            .map_err(|_| Box::new(Error::UnableToCreateFile) as Box<dyn std::error::Error>)?;
+    /// Redo the most recently undone change, returns whether there was one
+    pub fn redo(&mut self) -> Result<bool> {
+        let redone = self.history.redo(&mut self.map);
+        if redone {
+            self.save()?;
+        }
+        Ok(redone)
+    }
+
+    /// Restore a backup, where 1 is the most recent one, over the current notes
+    pub fn restore(&mut self, backup: usize) -> Result<()> {
+        let after = self.storage.read_backup(backup)?;
+        self.run(Command::Replace {
+            before: self.map.to_owned(),
+            after,
+        })
+    }
 
-        serde_json::to_writer(file, &self.map).map_err(|_| Error::UnableToSaveFile.into())
+    /// Numbers of the backups that exist, from the most recent to the oldest
+    pub fn backups(&self) -> Vec<usize> {
+        self.storage.backups()
     }
 
     /// Add a new note to storage
     pub fn put(&mut self, note: Note) -> Result<()> {
-        self.map.push(note);
-        self.save()
+        let idx = self.map.len();
+        self.run(Command::Put { idx, note })
     }
 
     /// Delete a note from storage
     pub fn delete(&mut self, idx: usize) -> Result<()> {
-        self.map.remove(idx);
-        self.save()
+        let note = self.map[idx].to_owned();
+        self.run(Command::Delete { idx, note })
     }
 
     /// Get a note, by index, from storage
@@ -116,15 +206,358 @@ impl Notes {
     }
 
     /// Update a note in storage
+    /// The note that is passed in is swapped with the old version of it
     pub fn update(&mut self, note: &mut Note, idx: usize) -> Result<()> {
-        std::mem::swap(&mut self.map[idx], note);
-        self.save()
+        let before = self.map[idx].to_owned();
+        let after = std::mem::replace(note, before.to_owned());
+        self.run(Command::Update {
+            before: Box::new(before),
+            after: Box::new(after),
+        })
+    }
+
+    /// Move a note to another index
+    pub fn move_note(&mut self, from: usize, to: usize) -> Result<()> {
+        self.run(Command::Move { from, to })
+    }
+
+    /// Swap the places of two notes
+    pub fn swap(&mut self, a: usize, b: usize) -> Result<()> {
+        self.run(Command::Swap { a, b })
+    }
+
+    /// Get the index of a note, by id
+    pub fn position(&self, id: Uuid) -> Option<usize> {
+        self.map.iter().position(|n| n.id == id)
+    }
+
+    /// Get a note, by id, from storage
+    pub fn get_by_id(&self, id: Uuid) -> Option<&Note> {
+        self.map.iter().find(|n| n.id == id)
+    }
+
+    /// Update the note in storage with the same id
+    pub fn update_by_id(&mut self, mut note: Note) -> Result<()> {
+        let idx = self.position(note.id).ok_or(Error::NoteNotFound)?;
+        self.update(&mut note, idx)
+    }
+
+    /// Delete a note, by id, from storage
+    pub fn delete_by_id(&mut self, id: Uuid) -> Result<()> {
+        let idx = self.position(id).ok_or(Error::NoteNotFound)?;
+        self.delete(idx)
+    }
+
+    /// Find the id of the only note whose id starts with the prefix
+    /// Hyphens are optional, so the short id of a note is enough
+    pub fn resolve_id(&self, prefix: &str) -> std::result::Result<Uuid, Error> {
+        let prefix = prefix.replace('-', "").to_lowercase();
+        if prefix.is_empty() {
+            return Err(Error::NoteNotFound);
+        }
+        let mut matches = self
+            .map
+            .iter()
+            .filter(|n| n.id.simple().to_string().starts_with(&prefix));
+        match (matches.next(), matches.next()) {
+            (Some(note), None) => Ok(note.id),
+            (Some(_), Some(_)) => Err(Error::AmbiguousId),
+            (None, _) => Err(Error::NoteNotFound),
+        }
+    }
+
+    /// Get the notes, with their index, that match the query
+    pub fn query(&self, query: &Query) -> Vec<(usize, &Note)> {
+        self.map
+            .iter()
+            .enumerate()
+            .filter(|(_, note)| query.matches(note))
+            .collect()
+    }
+
+    /// All the categories that are used, sorted and without the empty category
+    pub fn categories(&self) -> Vec<String> {
+        let mut categories: Vec<String> = self
+            .map
+            .iter()
+            .map(|note| note.category.to_owned())
+            .filter(|category| !category.is_empty())
+            .collect();
+        categories.sort();
+        categories.dedup();
+        categories
+    }
+
+    /// Move every note in a category to another one, which merges them when it is already used
+    /// Undone as one change and returns the number of notes that were moved
+    pub fn rename_category(&mut self, from: &str, to: &str) -> Result<usize> {
+        if from == to {
+            return Ok(0);
+        }
+        let updated_at = Utc::now().to_rfc3339();
+        let commands: Vec<Command> = self
+            .map
+            .iter()
+            .filter(|note| note.category == from)
+            .map(|note| Command::Update {
+                before: Box::new(note.to_owned()),
+                after: Box::new(Note {
+                    category: to.to_owned(),
+                    updated_at: updated_at.to_owned(),
+                    ..note.to_owned()
+                }),
+            })
+            .collect();
+        let moved = commands.len();
+        if moved > 0 {
+            self.run(Command::Batch(commands))?;
+        }
+        Ok(moved)
+    }
+
+    /// Move a note, together with its descendants, to another category
+    pub fn set_category(&mut self, idx: usize, category: &str) -> Result<()> {
+        let mut indexes = self.descendants(self.map[idx].id);
+        indexes.push(idx);
+        let updated_at = Utc::now().to_rfc3339();
+        let commands: Vec<Command> = indexes
+            .into_iter()
+            .map(|idx| &self.map[idx])
+            .filter(|note| note.category != category)
+            .map(|note| Command::Update {
+                before: Box::new(note.to_owned()),
+                after: Box::new(Note {
+                    category: category.to_owned(),
+                    updated_at: updated_at.to_owned(),
+                    ..note.to_owned()
+                }),
+            })
+            .collect();
+        if commands.is_empty() {
+            return Ok(());
+        }
+        self.run(Command::Batch(commands))
+    }
+
+    /// Indexes of the direct children of a note
+    pub fn children(&self, id: Uuid) -> Vec<usize> {
+        (0..self.map.len())
+            .filter(|idx| self.map[*idx].parent == Some(id))
+            .collect()
+    }
+
+    /// Indexes of the children of a note, their children and so on
+    pub fn descendants(&self, id: Uuid) -> Vec<usize> {
+        let mut descendants = Vec::new();
+        let mut ids = vec![id];
+        let mut seen = HashSet::from([id]);
+        while let Some(id) = ids.pop() {
+            for idx in self.children(id) {
+                if seen.insert(self.map[idx].id) {
+                    ids.push(self.map[idx].id);
+                    descendants.push(idx);
+                }
+            }
+        }
+        descendants.sort_unstable();
+        descendants
+    }
+
+    /// Move a note under another note, or to the top level without a parent
+    pub fn move_under(&mut self, idx: usize, parent: Option<Uuid>) -> Result<()> {
+        let id = self.map[idx].id;
+        if let Some(parent) = parent {
+            let invalid = parent == id
+                || self
+                    .descendants(id)
+                    .iter()
+                    .any(|d| self.map[*d].id == parent)
+                || self.get_by_id(parent).is_none();
+            if invalid {
+                return Err(Error::InvalidParent.into());
+            }
+        }
+        let mut note = Note {
+            parent,
+            updated_at: Utc::now().to_rfc3339(),
+            ..self.map[idx].to_owned()
+        };
+        self.update(&mut note, idx)
+    }
+
+    /// Move a note one level up, next to its parent
+    pub fn outdent(&mut self, idx: usize) -> Result<()> {
+        let grandparent = self.map[idx]
+            .parent
+            .and_then(|parent| self.get_by_id(parent))
+            .and_then(|parent| parent.parent);
+        self.move_under(idx, grandparent)
+    }
+
+    /// Delete a note with all of its descendants
+    pub fn delete_subtree(&mut self, idx: usize) -> Result<()> {
+        let mut indexes = self.descendants(self.map[idx].id);
+        indexes.push(idx);
+        indexes.sort_unstable();
+        // delete from the back, so every index is still valid when it is undone
+        let commands = indexes
+            .into_iter()
+            .rev()
+            .map(|idx| Command::Delete {
+                idx,
+                note: self.map[idx].to_owned(),
+            })
+            .collect();
+        self.run(Command::Batch(commands))
+    }
+
+    /// Order some of the notes as a tree, where children follow their parent in the order of
+    /// the notes
+    /// A note whose parent is not one of the notes is shown at the top level and the children
+    /// of collapsed notes are left out
+    pub fn tree(&self, indexes: &[usize], collapsed: &HashSet<Uuid>) -> Vec<TreeRow> {
+        let ids: HashSet<Uuid> = indexes.iter().map(|idx| self.map[*idx].id).collect();
+        let mut children: HashMap<Option<Uuid>, Vec<usize>> = HashMap::new();
+        for idx in indexes {
+            let parent = self.map[*idx].parent.filter(|p| ids.contains(p));
+            children.entry(parent).or_default().push(*idx);
+        }
+
+        let mut rows = Vec::new();
+        let mut seen = HashSet::new();
+        let roots = children.get(&None).into_iter().flatten();
+        // notes in a cycle of parents can not be reached from the top level, so they start there
+        for start in roots.chain(indexes) {
+            let mut stack = vec![(*start, 0, true)];
+            while let Some((idx, depth, visible)) = stack.pop() {
+                if !seen.insert(idx) {
+                    continue;
+                }
+                let id = self.map[idx].id;
+                let kids = children
+                    .get(&Some(id))
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                if visible {
+                    rows.push(TreeRow {
+                        idx,
+                        depth,
+                        has_children: !kids.is_empty(),
+                    });
+                }
+                let expanded = visible && !collapsed.contains(&id);
+                stack.extend(kids.iter().rev().map(|kid| (*kid, depth + 1, expanded)));
+            }
+        }
+        rows
+    }
+
+    /// Move a note to the archive
+    pub fn archive(&mut self, idx: usize) -> Result<()> {
+        let mut note = Note {
+            archived_at: Some(Utc::now().to_rfc3339()),
+            ..self.map[idx].to_owned()
+        };
+        self.update(&mut note, idx)
+    }
+
+    /// Restore a note from the archive
+    pub fn unarchive(&mut self, idx: usize) -> Result<()> {
+        let mut note = Note {
+            archived_at: None,
+            updated_at: Utc::now().to_rfc3339(),
+            ..self.map[idx].to_owned()
+        };
+        self.update(&mut note, idx)
+    }
+
+    /// Archive every done note that has not been updated for some time
+    /// Undone with the most recent change, only saves when at least one note changed and returns
+    /// whether that happened
+    pub fn archive_finished(&mut self, after: Duration) -> Result<bool> {
+        let now = Utc::now();
+        // no note can be older than the earliest date there is
+        let before = match now.checked_sub_signed(after) {
+            Some(before) => before,
+            None => return Ok(false),
+        };
+        let archived_at = now.to_rfc3339();
+        let commands: Vec<Command> = self
+            .map
+            .iter()
+            .filter(|n| {
+                n.state == State::Done
+                    && !n.is_archived()
+                    && DateTime::parse_from_rfc3339(&n.updated_at).is_ok_and(|d| d < before)
+            })
+            .map(|note| Command::Update {
+                before: Box::new(note.to_owned()),
+                after: Box::new(Note {
+                    archived_at: Some(archived_at.to_owned()),
+                    ..note.to_owned()
+                }),
+            })
+            .collect();
+        if commands.is_empty() {
+            return Ok(false);
+        }
+        self.run_amend(Command::Batch(commands))?;
+        Ok(true)
+    }
+
+    /// Set every overdue note, that is not done yet, to `State::Expired`
+    /// Undone with the most recent change, only saves when at least one note changed and returns
+    /// whether that happened
+    pub fn expire_overdue(&mut self) -> Result<bool> {
+        let now = Utc::now();
+        let updated_at = now.to_rfc3339();
+        let commands: Vec<Command> = self
+            .map
+            .iter()
+            .filter(|n| n.is_overdue(now))
+            .map(|note| Command::Update {
+                before: Box::new(note.to_owned()),
+                after: Box::new(Note {
+                    state: State::Expired,
+                    updated_at: updated_at.to_owned(),
+                    ..note.to_owned()
+                }),
+            })
+            .collect();
+        if commands.is_empty() {
+            return Ok(false);
+        }
+        self.run_amend(Command::Batch(commands))?;
+        Ok(true)
+    }
+}
+
+/// A single item on the checklist of a note
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChecklistItem {
+    /// What has to be done
+    pub title: String,
+
+    /// Whether it has been done
+    pub done: bool,
+}
+
+impl ChecklistItem {
+    /// Create a new item that still has to be done
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            done: false,
+        }
     }
 }
 
 /// A note / todo
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Note {
+    /// Unique and persistent id of the note
+    pub id: Uuid,
+
     /// Title of the note as displayed to the user
     pub title: String,
 
@@ -134,12 +567,30 @@ pub struct Note {
     /// The category of the note
     pub category: String,
 
-    // /// When the expiry date hits
-    // ends_at: Option<String>,
-    // /// Tags to categorize and filter on by the user
-    // tags: Vec<String>,
-    // /// Custom description in `md` format
-    // description: Option<Path>,
+    /// When the expiry date hits
+    #[serde(default)]
+    pub ends_at: Option<String>,
+
+    /// Tags to categorize and filter on by the user
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Custom description in `md` format
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// When the note was archived, archived notes are hidden from the notes
+    #[serde(default)]
+    pub archived_at: Option<String>,
+
+    /// Ordered list of smaller tasks that are part of the note
+    #[serde(default)]
+    pub checklist: Vec<ChecklistItem>,
+
+    /// Id of the note this note is a child of
+    #[serde(default)]
+    pub parent: Option<Uuid>,
+
     /// When the note was created
     pub created_at: String,
 
@@ -151,11 +602,362 @@ impl Note {
     /// Create a new note with a title
     pub fn new(title: impl Into<String>, category: String, state: State) -> Self {
         Self {
+            id: Uuid::new_v4(),
             title: title.into(),
             state,
             category,
+            ends_at: None,
+            tags: Vec::new(),
+            description: None,
+            archived_at: None,
+            checklist: Vec::new(),
+            parent: None,
             created_at: Utc::now().to_rfc3339(),
             updated_at: Utc::now().to_rfc3339(),
         }
     }
+
+    /// Short form of the id, as shown to the user
+    pub fn short_id(&self) -> String {
+        self.id.simple().to_string()[..8].to_owned()
+    }
+
+    /// Parse a `YYYY-MM-DD` date, which ends at the end of that local day, into an expiry date
+    /// An empty input means that there is no expiry date
+    pub fn parse_ends_at(input: &str) -> std::result::Result<Option<String>, Error> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(None);
+        }
+        let end_of_day = NaiveDate::parse_from_str(input, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(23, 59, 59))
+            .ok_or(Error::InvalidDate)?;
+        let ends_at = Local
+            .from_local_datetime(&end_of_day)
+            .earliest()
+            .map(|d| d.with_timezone(&Utc).to_rfc3339());
+        Ok(ends_at)
+    }
+
+    /// Parsed expiry date of the note, if it has a valid one
+    pub fn due_date(&self) -> Option<DateTime<Utc>> {
+        let ends_at = self.ends_at.as_ref()?;
+        DateTime::parse_from_rfc3339(ends_at)
+            .ok()
+            .map(|d| d.with_timezone(&Utc))
+    }
+
+    /// Whether the note has any, or all, of the tags
+    /// An empty set of tags matches every note
+    pub fn has_tags(&self, tags: &[String], mode: TagMatch) -> bool {
+        let mut matches = tags.iter().map(|tag| self.tags.contains(tag));
+        match mode {
+            TagMatch::Any => tags.is_empty() || matches.any(|m| m),
+            TagMatch::All => matches.all(|m| m),
+        }
+    }
+
+    /// Number of finished and total checklist items, if the note has a checklist
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        if self.checklist.is_empty() {
+            return None;
+        }
+        let done = self.checklist.iter().filter(|item| item.done).count();
+        Some((done, self.checklist.len()))
+    }
+
+    /// How well the note matches a fuzzy search on its title, category and tags, if it does
+    pub fn search_score(&self, query: &str) -> Option<i64> {
+        std::iter::once(&self.title)
+            .chain(std::iter::once(&self.category))
+            .chain(&self.tags)
+            .filter_map(|text| fuzzy_match(query, text))
+            .map(|m| m.score)
+            .max()
+    }
+
+    /// Whether the note is in the archive
+    pub fn is_archived(&self) -> bool {
+        self.archived_at.is_some()
+    }
+
+    /// Whether the expiry date has passed while the note is not finished
+    pub fn is_overdue(&self, now: DateTime<Utc>) -> bool {
+        match self.state {
+            State::Done | State::Expired => false,
+            _ => self.due_date().is_some_and(|due| due < now),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::MemoryStorage;
+
+    /// Notes stored in memory, with the storage to inspect what was saved
+    fn notes(titles: &[&str]) -> (Notes, MemoryStorage) {
+        let map = titles
+            .iter()
+            .map(|title| Note::new(*title, String::new(), State::Todo))
+            .collect();
+        let storage = MemoryStorage::new(map);
+        let notes = Notes::with_storage(Box::new(storage.clone())).unwrap();
+        (notes, storage)
+    }
+
+    #[test]
+    fn put_and_delete_are_saved() {
+        let (mut notes, storage) = notes(&["a"]);
+        notes
+            .put(Note::new("b", String::new(), State::Todo))
+            .unwrap();
+        assert_eq!(storage.notes().len(), 2);
+        notes.delete(0).unwrap();
+        assert_eq!(storage.notes(), notes.map);
+        assert_eq!(storage.notes()[0].title, "b");
+    }
+
+    #[test]
+    fn overdue_notes_expire_on_load() {
+        let mut note = Note::new("a", String::new(), State::Todo);
+        note.ends_at = Some(String::from("2000-01-01T00:00:00+00:00"));
+        let storage = MemoryStorage::new(vec![note]);
+        let mut notes = Notes::with_storage(Box::new(storage.clone())).unwrap();
+        assert_eq!(notes.map[0].state, State::Expired);
+        assert_eq!(storage.notes()[0].state, State::Expired);
+        assert!(!notes.expire_overdue().unwrap());
+        assert!(!notes.undo().unwrap());
+    }
+
+    #[test]
+    fn expiring_is_undone_with_the_change_before() {
+        let (mut notes, storage) = notes(&["a"]);
+        let note = Note {
+            ends_at: Some(String::from("2000-01-01T00:00:00+00:00")),
+            ..notes.map[0].to_owned()
+        };
+        notes.update_by_id(note).unwrap();
+        assert!(notes.expire_overdue().unwrap());
+        assert_eq!(storage.notes()[0].state, State::Expired);
+
+        assert!(notes.undo().unwrap());
+        assert_eq!(storage.notes()[0].state, State::Todo);
+        assert_eq!(storage.notes()[0].ends_at, None);
+        assert!(notes.redo().unwrap());
+        assert_eq!(storage.notes()[0].state, State::Expired);
+    }
+
+    #[test]
+    fn resolve_id_by_prefix() {
+        let (notes, _) = notes(&["a", "b"]);
+        let id = notes.map[1].id;
+        assert_eq!(notes.resolve_id(&notes.map[1].short_id()).unwrap(), id);
+        assert!(matches!(notes.resolve_id(""), Err(Error::NoteNotFound)));
+    }
+
+    #[test]
+    fn undo_and_redo_are_saved() {
+        let (mut notes, storage) = notes(&["a", "b"]);
+        notes.delete(0).unwrap();
+        notes.move_note(0, 0).unwrap();
+        assert!(notes.undo().unwrap());
+        assert!(notes.undo().unwrap());
+        assert!(!notes.undo().unwrap());
+        assert_eq!(storage.notes().len(), 2);
+        assert!(notes.redo().unwrap());
+        assert_eq!(storage.notes().len(), 1);
+    }
+
+    #[test]
+    fn undo_update() {
+        let (mut notes, storage) = notes(&["a"]);
+        let mut note = Note {
+            title: String::from("b"),
+            ..notes.map[0].to_owned()
+        };
+        notes.update(&mut note, 0).unwrap();
+        assert_eq!(note.title, "a");
+        assert!(notes.undo().unwrap());
+        assert_eq!(storage.notes()[0].title, "a");
+    }
+
+    #[test]
+    fn archive_and_unarchive() {
+        let (mut notes, storage) = notes(&["a"]);
+        notes.archive(0).unwrap();
+        assert!(storage.notes()[0].is_archived());
+        notes.unarchive(0).unwrap();
+        assert!(!storage.notes()[0].is_archived());
+    }
+
+    #[test]
+    fn archive_finished_only_old_done_notes() {
+        let (mut notes, _) = notes(&["old", "new", "todo"]);
+        let old = (Utc::now() - Duration::days(10)).to_rfc3339();
+        notes.map[0].state = State::Done;
+        notes.map[0].updated_at = old.to_owned();
+        notes.map[1].state = State::Done;
+        notes.map[2].updated_at = old;
+        assert!(notes.archive_finished(Duration::days(7)).unwrap());
+        let archived: Vec<bool> = notes.map.iter().map(Note::is_archived).collect();
+        assert_eq!(archived, [true, false, false]);
+        assert!(!notes.archive_finished(Duration::days(7)).unwrap());
+        assert!(!notes.archive_finished(Duration::MAX).unwrap());
+    }
+
+    #[test]
+    fn progress_of_checklist() {
+        let mut note = Note::new("a", String::new(), State::Todo);
+        assert_eq!(note.progress(), None);
+        note.checklist = vec![ChecklistItem::new("b"), ChecklistItem::new("c")];
+        note.checklist[1].done = true;
+        assert_eq!(note.progress(), Some((1, 2)));
+    }
+
+    /// Make the note at `child` a child of the note at `parent`
+    fn nest(notes: &mut Notes, child: usize, parent: usize) {
+        let parent = notes.map[parent].id;
+        notes.move_under(child, Some(parent)).unwrap();
+    }
+
+    #[test]
+    fn tree_orders_children_after_parent() {
+        let (mut notes, _) = notes(&["a", "b", "c", "d"]);
+        nest(&mut notes, 2, 0);
+        nest(&mut notes, 3, 2);
+        let rows = notes.tree(&[0, 1, 2, 3], &HashSet::new());
+        let order: Vec<(usize, usize)> = rows.iter().map(|r| (r.idx, r.depth)).collect();
+        assert_eq!(order, [(0, 0), (2, 1), (3, 2), (1, 0)]);
+        assert!(rows[0].has_children);
+        assert!(!rows[2].has_children);
+    }
+
+    #[test]
+    fn tree_hides_collapsed_and_lifts_orphans() {
+        let (mut notes, _) = notes(&["a", "b", "c"]);
+        nest(&mut notes, 1, 0);
+        nest(&mut notes, 2, 1);
+        let collapsed = HashSet::from([notes.map[0].id]);
+        let rows = notes.tree(&[0, 1, 2], &collapsed);
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].has_children);
+        // the parent is filtered out, so the child is at the top level
+        let rows = notes.tree(&[2], &HashSet::new());
+        assert_eq!((rows[0].idx, rows[0].depth), (2, 0));
+    }
+
+    #[test]
+    fn move_under_rejects_cycles() {
+        let (mut notes, _) = notes(&["a", "b"]);
+        nest(&mut notes, 1, 0);
+        let child = notes.map[1].id;
+        assert!(notes.move_under(0, Some(child)).is_err());
+        assert!(notes.move_under(0, Some(notes.map[0].id)).is_err());
+        notes.outdent(1).unwrap();
+        assert_eq!(notes.map[1].parent, None);
+    }
+
+    #[test]
+    fn delete_subtree_and_undo() {
+        let (mut notes, storage) = notes(&["a", "b", "c", "d"]);
+        nest(&mut notes, 1, 0);
+        nest(&mut notes, 3, 1);
+        let before = notes.map.to_owned();
+        notes.delete_subtree(0).unwrap();
+        assert_eq!(storage.notes().len(), 1);
+        assert_eq!(storage.notes()[0].title, "c");
+        notes.undo().unwrap();
+        assert_eq!(notes.map, before);
+    }
+
+    #[test]
+    fn tags_match_any_or_all() {
+        let note = Note {
+            tags: vec![String::from("a"), String::from("b")],
+            ..Note::new("n", String::new(), State::Todo)
+        };
+        let tags = |tags: &[&str]| -> Vec<String> { tags.iter().map(|t| t.to_string()).collect() };
+        assert!(note.has_tags(&tags(&["a", "c"]), TagMatch::Any));
+        assert!(!note.has_tags(&tags(&["a", "c"]), TagMatch::All));
+        assert!(note.has_tags(&tags(&["b", "a"]), TagMatch::All));
+        assert!(!note.has_tags(&tags(&["c"]), TagMatch::Any));
+        // an empty set of tags matches every note, also one without tags
+        let untagged = Note::new("u", String::new(), State::Todo);
+        for mode in [TagMatch::Any, TagMatch::All] {
+            assert!(note.has_tags(&[], mode));
+            assert!(untagged.has_tags(&[], mode));
+            assert!(!untagged.has_tags(&tags(&["a"]), mode));
+        }
+    }
+
+    #[test]
+    fn query_notes_by_tags() {
+        let (mut notes, _) = notes(&["a", "ab", "none"]);
+        notes.map[0].tags = vec![String::from("a")];
+        notes.map[1].tags = vec![String::from("a"), String::from("b")];
+        let titles = |tags: &[&str], tag_match: TagMatch| -> Vec<String> {
+            let query = Query {
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+                tag_match,
+                ..Query::default()
+            };
+            notes
+                .query(&query)
+                .into_iter()
+                .map(|(_, n)| n.title.to_owned())
+                .collect()
+        };
+        assert_eq!(titles(&["a", "b"], TagMatch::Any), ["a", "ab"]);
+        assert_eq!(titles(&["a", "b"], TagMatch::All), ["ab"]);
+        assert_eq!(titles(&["c"], TagMatch::Any), Vec::<String>::new());
+        assert_eq!(titles(&[], TagMatch::Any), ["a", "ab", "none"]);
+        assert_eq!(titles(&[], TagMatch::All), ["a", "ab", "none"]);
+    }
+
+    #[test]
+    fn search_title_category_and_tags() {
+        let note = Note {
+            tags: vec![String::from("errands")],
+            ..Note::new("buy bread", String::from("home"), State::Todo)
+        };
+        assert!(note.search_score("brd").is_some());
+        assert!(note.search_score("hme").is_some());
+        assert!(note.search_score("errnd").is_some());
+        assert!(note.search_score("xyz").is_none());
+    }
+
+    #[test]
+    fn rename_category_merges_and_undoes_as_one() {
+        let (mut notes, storage) = notes(&["a", "b", "c"]);
+        notes.map[0].category = String::from("wrok");
+        notes.map[1].category = String::from("work");
+        notes.map[2].category = String::from("wrok");
+        assert_eq!(notes.rename_category("wrok", "work").unwrap(), 2);
+        assert_eq!(notes.categories(), ["work"]);
+        assert!(storage.notes().iter().all(|n| n.category == "work"));
+        notes.undo().unwrap();
+        assert_eq!(notes.categories(), ["work", "wrok"]);
+        assert_eq!(notes.rename_category("home", "work").unwrap(), 0);
+    }
+
+    #[test]
+    fn set_category_moves_the_descendants() {
+        let (mut notes, storage) = notes(&["parent", "child", "grandchild", "other"]);
+        notes.map[1].parent = Some(notes.map[0].id);
+        notes.map[2].parent = Some(notes.map[1].id);
+        notes.set_category(0, "work").unwrap();
+        let categories: Vec<String> = storage.notes().into_iter().map(|n| n.category).collect();
+        assert_eq!(categories, ["work", "work", "work", ""]);
+        notes.undo().unwrap();
+        assert!(notes.map.iter().all(|n| n.category.is_empty()));
+    }
+
+    #[test]
+    fn memory_storage_has_no_backups() {
+        let (mut notes, _) = notes(&["a"]);
+        assert!(notes.backups().is_empty());
+        assert!(notes.restore(1).is_err());
+    }
 }
@@ -0,0 +1,232 @@
+use chrono::DateTime;
+use std::cmp::Ordering;
+
+use crate::note::{Note, State, TagMatch};
+
+/// Which notes to get from `Notes::query`, a note has to match every condition that is set
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    /// States the note can have, any state when empty
+    pub states: Vec<State>,
+
+    /// Category the note has to be in
+    pub category: Option<String>,
+
+    /// Tags the note has to have, no tags are required when empty
+    pub tags: Vec<String>,
+
+    /// Whether the note needs any or all of the tags
+    pub tag_match: TagMatch,
+
+    /// Whether the note has to be archived, or not archived
+    pub archived: Option<bool>,
+
+    /// Fuzzy search on the title, category and tags of the note
+    pub search: String,
+}
+
+impl Query {
+    /// Whether the note matches the query
+    pub fn matches(&self, note: &Note) -> bool {
+        (self.states.is_empty() || self.states.contains(&note.state))
+            && self.category.as_ref().is_none_or(|c| &note.category == c)
+            && note.has_tags(&self.tags, self.tag_match)
+            && self.archived.is_none_or(|a| note.is_archived() == a)
+            && (self.search.trim().is_empty() || note.search_score(&self.search).is_some())
+    }
+
+    /// Whether the query matches every note that is not archived, or archived
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+            && self.category.is_none()
+            && self.tags.is_empty()
+            && self.search.trim().is_empty()
+    }
+}
+
+/// What the notes are sorted on
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SortKey {
+    /// The order the notes were put in by the user
+    #[default]
+    Manual,
+
+    /// State, in the order of the states
+    State,
+
+    /// Category, alphabetically
+    Category,
+
+    /// When the note was created
+    Created,
+
+    /// When the note was last updated
+    Updated,
+
+    /// Due date, where notes without one come last
+    Due,
+}
+
+impl SortKey {
+    /// All the keys, in the order they are switched through
+    pub const ALL: [SortKey; 6] = [
+        SortKey::Manual,
+        SortKey::State,
+        SortKey::Category,
+        SortKey::Created,
+        SortKey::Updated,
+        SortKey::Due,
+    ];
+
+    /// The key that comes after this one
+    pub fn next(&self) -> SortKey {
+        let i = SortKey::ALL.iter().position(|k| k == self).unwrap_or(0);
+        SortKey::ALL[(i + 1) % SortKey::ALL.len()]
+    }
+
+    /// Name of the key as shown to the user
+    pub fn name(&self) -> &'static str {
+        match self {
+            SortKey::Manual => "manual",
+            SortKey::State => "state",
+            SortKey::Category => "category",
+            SortKey::Created => "created",
+            SortKey::Updated => "updated",
+            SortKey::Due => "due",
+        }
+    }
+}
+
+/// How the notes are sorted, without changing the order they are stored in
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Sort {
+    /// What the notes are sorted on
+    pub key: SortKey,
+
+    /// Whether the notes are sorted from high to low
+    pub descending: bool,
+}
+
+impl Sort {
+    /// Compare two notes, notes that are equal keep their manual order when sorted stably
+    pub fn compare(&self, a: &Note, b: &Note) -> Ordering {
+        let ordering = match self.key {
+            SortKey::Manual => Ordering::Equal,
+            SortKey::State => a.state.cmp(&b.state),
+            SortKey::Category => a.category.to_lowercase().cmp(&b.category.to_lowercase()),
+            SortKey::Created => compare_dates(&a.created_at, &b.created_at),
+            SortKey::Updated => compare_dates(&a.updated_at, &b.updated_at),
+            // notes without a due date come last, also when sorting descending
+            SortKey::Due => match (a.due_date(), b.due_date()) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => return Ordering::Less,
+                (None, Some(_)) => return Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        };
+        match self.descending {
+            true => ordering.reverse(),
+            false => ordering,
+        }
+    }
+
+    /// Sort indexes of the notes, in place
+    pub fn sort(&self, indexes: &mut [usize], notes: &[Note]) {
+        if self.key != SortKey::Manual {
+            indexes.sort_by(|a, b| self.compare(&notes[*a], &notes[*b]));
+        }
+    }
+}
+
+/// Compare two RFC 3339 dates, dates that can not be parsed come first
+fn compare_dates(a: &str, b: &str) -> Ordering {
+    let a = DateTime::parse_from_rfc3339(a).ok();
+    let b = DateTime::parse_from_rfc3339(b).ok();
+    a.cmp(&b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A note with a state and category
+    fn note(state: State, category: &str) -> Note {
+        Note::new("title", category.to_owned(), state)
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        let query = Query::default();
+        assert!(query.is_empty());
+        assert!(query.matches(&note(State::Done, "work")));
+    }
+
+    #[test]
+    fn states_match_any() {
+        let query = Query {
+            states: vec![State::Todo, State::InProgress],
+            ..Query::default()
+        };
+        assert!(query.matches(&note(State::InProgress, "")));
+        assert!(!query.matches(&note(State::Done, "")));
+    }
+
+    #[test]
+    fn sort_keeps_manual_order_of_equal_notes() {
+        let notes = vec![
+            note(State::Done, "a"),
+            note(State::Todo, "b"),
+            note(State::Done, "c"),
+            note(State::Todo, "d"),
+        ];
+        let mut indexes = vec![0, 1, 2, 3];
+        let sort = Sort {
+            key: SortKey::State,
+            descending: false,
+        };
+        sort.sort(&mut indexes, &notes);
+        assert_eq!(indexes, [1, 3, 0, 2]);
+        let sort = Sort {
+            descending: true,
+            ..sort
+        };
+        sort.sort(&mut indexes, &notes);
+        assert_eq!(indexes, [0, 2, 1, 3]);
+    }
+
+    #[test]
+    fn sort_on_due_puts_notes_without_one_last() {
+        let mut notes = vec![
+            note(State::Todo, ""),
+            note(State::Todo, ""),
+            note(State::Todo, ""),
+        ];
+        notes[1].ends_at = Note::parse_ends_at("2022-06-02").unwrap();
+        notes[2].ends_at = Note::parse_ends_at("2022-06-01").unwrap();
+        for (descending, expected) in [(false, [2, 1, 0]), (true, [1, 2, 0])] {
+            let mut indexes = vec![0, 1, 2];
+            let sort = Sort {
+                key: SortKey::Due,
+                descending,
+            };
+            sort.sort(&mut indexes, &notes);
+            assert_eq!(indexes, expected);
+        }
+    }
+
+    #[test]
+    fn all_conditions_have_to_match() {
+        let query = Query {
+            states: vec![State::Todo],
+            category: Some(String::from("work")),
+            archived: Some(false),
+            ..Query::default()
+        };
+        assert!(query.matches(&note(State::Todo, "work")));
+        assert!(!query.matches(&note(State::Todo, "home")));
+        assert!(!query.matches(&note(State::Done, "work")));
+        let mut archived = note(State::Todo, "work");
+        archived.archived_at = Some(String::from("2022-06-01T12:00:00+00:00"));
+        assert!(!query.matches(&archived));
+    }
+}
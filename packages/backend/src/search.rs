@@ -0,0 +1,83 @@
+/// Where a query matched a text and how well
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    /// Higher is a better match
+    pub score: i64,
+
+    /// Indexes of the characters of the text that matched the query
+    pub positions: Vec<usize>,
+}
+
+/// Match the characters of the query, in order but not necessarily next to each other, against
+/// the text, ignoring case and whitespace in the query
+/// Consecutive characters and characters at the start of a word score higher
+pub fn fuzzy_match(query: &str, text: &str) -> Option<FuzzyMatch> {
+    let mut query = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .peekable();
+    query.peek()?;
+
+    let mut score = 0;
+    let mut positions: Vec<usize> = Vec::new();
+    let mut previous: Option<char> = None;
+    for (i, c) in text.chars().enumerate() {
+        let wanted = match query.peek() {
+            Some(wanted) => *wanted,
+            None => break,
+        };
+        if c.to_lowercase().eq(std::iter::once(wanted)) {
+            score += 1;
+            if positions.last().is_some_and(|last| last + 1 == i) {
+                score += 5;
+            }
+            if previous.is_none_or(|p| !p.is_alphanumeric()) {
+                score += 3;
+            }
+            positions.push(i);
+            query.next();
+        }
+        previous = Some(c);
+    }
+    if query.peek().is_some() {
+        return None;
+    }
+    // prefer matches that start early in the text
+    score -= positions.first().copied().unwrap_or_default().min(10) as i64;
+    Some(FuzzyMatch { score, positions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_in_order_ignoring_case() {
+        let m = fuzzy_match("Bd", "buy bread").unwrap();
+        assert_eq!(m.positions, [0, 8]);
+        assert!(fuzzy_match("db", "buy bread").is_none());
+    }
+
+    #[test]
+    fn empty_query_matches_nothing() {
+        assert!(fuzzy_match("", "text").is_none());
+        assert!(fuzzy_match(" ", "text").is_none());
+    }
+
+    #[test]
+    fn consecutive_and_word_starts_score_higher() {
+        let consecutive = fuzzy_match("bre", "bread").unwrap();
+        let scattered = fuzzy_match("bre", "bakery record").unwrap();
+        assert!(consecutive.score > scattered.score);
+        let word_start = fuzzy_match("r", "a read").unwrap();
+        let inside = fuzzy_match("r", "a bread").unwrap();
+        assert!(word_start.score > inside.score);
+    }
+
+    #[test]
+    fn positions_are_characters() {
+        let m = fuzzy_match("é", "café").unwrap();
+        assert_eq!(m.positions, [3]);
+    }
+}
@@ -0,0 +1,395 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use super::Storage;
+use crate::error::{Error, Result};
+use crate::migration::{self, CURRENT_VERSION};
+use crate::note::Note;
+
+/// Notes as they are stored in a file, with the version of the format
+#[derive(Serialize, Deserialize)]
+pub(crate) struct StoredNotes {
+    /// Version of the format, see `migration`
+    pub version: u64,
+
+    /// The actual notes
+    pub notes: Vec<Note>,
+}
+
+/// Notes stored in a JSON file, with a number of backups next to it
+#[derive(Debug)]
+pub struct JsonStorage {
+    /// File path of the notes
+    path: PathBuf,
+
+    /// Number of backups that are kept of the file
+    backups: usize,
+
+    /// Why the notes had to be restored from a backup while loading, if they had to be
+    recovery: Option<Error>,
+}
+
+impl JsonStorage {
+    /// Storage for a file, keeping a number of backups of it on every save
+    pub fn new(path: impl Into<PathBuf>, backups: usize) -> Self {
+        Self {
+            path: path.into(),
+            backups,
+            recovery: None,
+        }
+    }
+
+    /// Move the file to a timestamped copy next to it, e.g. `notes.json.corrupt-20220601T120000`
+    /// An earlier copy with the same timestamp is kept, the copy gets a suffix like `-1` instead
+    fn quarantine(&self) -> Result<PathBuf> {
+        let timestamp = Local::now().format("%Y%m%dT%H%M%S").to_string();
+        let mut path = self.sibling_path(&format!("corrupt-{}", timestamp));
+        let mut n = 0;
+        while path.exists() {
+            n += 1;
+            path = self.sibling_path(&format!("corrupt-{}-{}", timestamp, n));
+        }
+        fs::rename(&self.path, &path)?;
+        Ok(path)
+    }
+
+    /// Read the notes from a file, upgraded to the current version, and the version they were
+    /// stored in
+    /// A missing or empty file has no notes
+    fn read(path: &Path) -> Result<(Vec<Note>, u64)> {
+        if !path.exists() || fs::metadata(path)?.len() == 0 {
+            return Ok((Vec::new(), CURRENT_VERSION));
+        }
+        let reader = BufReader::new(fs::File::open(path)?);
+        let (value, version) = migration::migrate(serde_json::from_reader(reader)?)?;
+        let stored: StoredNotes = serde_json::from_value(value)?;
+        Ok((stored.notes, version))
+    }
+
+    /// Create a new temporary file next to the notes, e.g. `notes.json.tmp-1234-0`
+    /// The process id and a counter keep it from being shared with another save
+    fn create_temp_file(&self) -> Result<(PathBuf, fs::File)> {
+        for attempt in 0..100 {
+            let path = self.sibling_path(&format!("tmp-{}-{}", process::id(), attempt));
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(file) => return Ok((path, file)),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(_) => break,
+            }
+        }
+        Err(Error::UnableToCreateFile.into())
+    }
+
+    /// Write the notes to a file, in the current version, and make sure they are on disk
+    fn write(file: &fs::File, notes: &[Note]) -> Result<()> {
+        let stored = StoredNotes {
+            version: CURRENT_VERSION,
+            notes: notes.to_owned(),
+        };
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, &stored).map_err(|_| Error::UnableToSaveFile)?;
+        writer.flush().map_err(|_| Error::UnableToSaveFile)?;
+        drop(writer);
+        file.sync_all().map_err(|_| Error::UnableToSaveFile)?;
+        Ok(())
+    }
+
+    /// Shift all the backups by one, dropping the oldest, and back up the current file
+    fn rotate_backups(&self) -> Result<()> {
+        if self.backups == 0 || !self.path.exists() {
+            return Ok(());
+        }
+        for n in (1..self.backups).rev() {
+            let from = self.backup_path(n);
+            if from.exists() {
+                fs::rename(from, self.backup_path(n + 1))?;
+            }
+        }
+        fs::copy(&self.path, self.backup_path(1))?;
+        Ok(())
+    }
+
+    /// File of a backup, e.g. `notes.json.1`
+    fn backup_path(&self, n: usize) -> PathBuf {
+        self.sibling_path(&n.to_string())
+    }
+
+    /// File next to the notes with an extra extension
+    fn sibling_path(&self, extension: &str) -> PathBuf {
+        let mut path = self.path.to_owned().into_os_string();
+        path.push(".");
+        path.push(extension);
+        PathBuf::from(path)
+    }
+
+    /// Make sure the rename of the file is persisted
+    /// Directories can only be synced on unix, and a failure only makes the save less durable
+    fn sync_dir(&self) {
+        #[cfg(unix)]
+        if let Some(dir) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            if let Ok(dir) = fs::File::open(dir) {
+                let _ = dir.sync_all();
+            }
+        }
+    }
+}
+
+impl Storage for JsonStorage {
+    /// Name of the file without extension
+    fn name(&self) -> String {
+        self.path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Load the notes from the file
+    /// When the file can not be parsed, the most recent backup that can be parsed is restored
+    fn load(&mut self) -> Result<Vec<Note>> {
+        if let Some(prefix) = self.path.parent() {
+            // create all the required directories
+            fs::create_dir_all(prefix)?;
+        }
+
+        let (notes, version) = match JsonStorage::read(&self.path) {
+            Ok(read) => read,
+            Err(e) if e.is::<serde_json::Error>() => {
+                // move the corrupt file out of the way, so it can not be overwritten
+                let quarantined = self.quarantine()?;
+                let backup = self
+                    .backups()
+                    .into_iter()
+                    .find(|n| JsonStorage::read(&self.backup_path(*n)).is_ok());
+                let error = Error::CorruptStorage {
+                    path: self.path.to_owned(),
+                    quarantined,
+                    backup,
+                };
+                match backup {
+                    Some(n) => {
+                        fs::copy(self.backup_path(n), &self.path)?;
+                        self.recovery = Some(error);
+                        JsonStorage::read(&self.path)?
+                    }
+                    None => return Err(error.into()),
+                }
+            }
+            Err(e) => return Err(e),
+        };
+        if version < CURRENT_VERSION {
+            // store the upgraded notes, the old file is kept as the most recent backup
+            self.save(&notes)?;
+        }
+        Ok(notes)
+    }
+
+    /// Save the notes to the file
+    /// The notes are written to a temporary file first, which replaces the file once it is
+    /// completely written, so a crash while saving can not lose the notes
+    fn save(&mut self, notes: &[Note]) -> Result<()> {
+        let (tmp, file) = self.create_temp_file()?;
+        let saved = JsonStorage::write(&file, notes)
+            .and_then(|()| self.rotate_backups())
+            .and_then(|()| fs::rename(&tmp, &self.path).map_err(Into::into));
+        if saved.is_err() {
+            // the notes are still in the file, so only the partial copy is removed
+            let _ = fs::remove_file(&tmp);
+            return saved;
+        }
+        self.sync_dir();
+        Ok(())
+    }
+
+    fn take_recovery(&mut self) -> Option<Error> {
+        self.recovery.take()
+    }
+
+    fn backups(&self) -> Vec<usize> {
+        (1..=self.backups)
+            .filter(|n| self.backup_path(*n).exists())
+            .collect()
+    }
+
+    fn read_backup(&self, backup: usize) -> Result<Vec<Note>> {
+        let path = self.backup_path(backup);
+        if backup == 0 || !path.exists() {
+            return Err(Error::BackupNotFound.into());
+        }
+        Ok(JsonStorage::read(&path)?.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::{Notes, State};
+    use crate::test_dir::TestDir;
+
+    /// Notes with the titles
+    fn notes(titles: &[&str]) -> Vec<Note> {
+        titles
+            .iter()
+            .map(|title| Note::new(*title, String::new(), State::Todo))
+            .collect()
+    }
+
+    /// Titles of the notes
+    fn titles(notes: &[Note]) -> Vec<String> {
+        notes.iter().map(|note| note.title.to_owned()).collect()
+    }
+
+    #[test]
+    fn save_replaces_the_file() {
+        let dir = TestDir::new();
+        let mut storage = JsonStorage::new(dir.join("notes.json"), 3);
+        storage.save(&notes(&["a"])).unwrap();
+        storage.save(&notes(&["b", "c"])).unwrap();
+        assert_eq!(titles(&storage.load().unwrap()), ["b", "c"]);
+
+        // no temporary files are left behind
+        let mut files: Vec<String> = fs::read_dir(&*dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["notes.json", "notes.json.1"]);
+    }
+
+    #[test]
+    fn temporary_files_are_not_shared() {
+        let dir = TestDir::new();
+        let storage = JsonStorage::new(dir.join("notes.json"), 3);
+        // a file that is left behind, or is being written by another save
+        let (left, _) = storage.create_temp_file().unwrap();
+        let (path, _) = storage.create_temp_file().unwrap();
+        assert_ne!(path, left);
+        assert_eq!(path.parent(), Some(&*dir));
+    }
+
+    #[test]
+    fn backups_are_rotated() {
+        let dir = TestDir::new();
+        let mut storage = JsonStorage::new(dir.join("notes.json"), 2);
+        for title in ["a", "b", "c", "d"] {
+            storage.save(&notes(&[title])).unwrap();
+        }
+        assert_eq!(storage.backups(), [1, 2]);
+        assert!(!storage.backup_path(3).exists());
+        assert_eq!(titles(&storage.read_backup(1).unwrap()), ["c"]);
+        assert_eq!(titles(&storage.read_backup(2).unwrap()), ["b"]);
+        assert!(storage.read_backup(0).is_err());
+        assert!(storage.read_backup(3).is_err());
+    }
+
+    #[test]
+    fn no_backups_are_kept_when_disabled() {
+        let dir = TestDir::new();
+        let mut storage = JsonStorage::new(dir.join("notes.json"), 0);
+        storage.save(&notes(&["a"])).unwrap();
+        storage.save(&notes(&["b"])).unwrap();
+        assert!(storage.backups().is_empty());
+        assert!(!storage.backup_path(1).exists());
+    }
+
+    #[test]
+    fn unreadable_backups_are_skipped() {
+        let dir = TestDir::new();
+        let mut storage = JsonStorage::new(dir.join("notes.json"), 3);
+        for title in ["a", "b", "c"] {
+            storage.save(&notes(&[title])).unwrap();
+        }
+        fs::write(&storage.path, "{").unwrap();
+        fs::write(storage.backup_path(1), "not json").unwrap();
+
+        assert_eq!(titles(&storage.load().unwrap()), ["a"]);
+        assert!(matches!(
+            storage.take_recovery(),
+            Some(Error::CorruptStorage {
+                backup: Some(2),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn corrupt_files_are_quarantined() {
+        let dir = TestDir::new();
+        let mut storage = JsonStorage::new(dir.join("notes.json"), 3);
+        storage.save(&notes(&["a"])).unwrap();
+        storage.save(&notes(&["b"])).unwrap();
+        fs::write(&storage.path, "{ corrupt").unwrap();
+
+        assert_eq!(titles(&storage.load().unwrap()), ["a"]);
+        let quarantined = match storage.take_recovery() {
+            Some(Error::CorruptStorage {
+                quarantined,
+                backup: Some(1),
+                ..
+            }) => quarantined,
+            e => panic!("unexpected recovery {:?}", e),
+        };
+        assert_eq!(fs::read_to_string(quarantined).unwrap(), "{ corrupt");
+        // the restored backup is stored as the notes
+        assert_eq!(titles(&JsonStorage::read(&storage.path).unwrap().0), ["a"]);
+    }
+
+    #[test]
+    fn quarantined_files_are_never_overwritten() {
+        let dir = TestDir::new();
+        let storage = JsonStorage::new(dir.join("notes.json"), 3);
+        let mut quarantined = Vec::new();
+        for content in ["first", "second", "third"] {
+            fs::write(&storage.path, content).unwrap();
+            quarantined.push(storage.quarantine().unwrap());
+        }
+        for (path, content) in quarantined.iter().zip(["first", "second", "third"]) {
+            assert_eq!(fs::read_to_string(path).unwrap(), content);
+        }
+    }
+
+    #[test]
+    fn corrupt_file_without_readable_backup_is_an_error() {
+        let dir = TestDir::new();
+        let mut storage = JsonStorage::new(dir.join("notes.json"), 3);
+        storage.save(&notes(&["a"])).unwrap();
+        storage.save(&notes(&["b"])).unwrap();
+        fs::write(&storage.path, "{").unwrap();
+        fs::write(storage.backup_path(1), "{").unwrap();
+
+        let error = storage.load().unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<Error>(),
+            Some(Error::CorruptStorage { backup: None, .. })
+        ));
+        // the corrupt file is moved aside, so it can not be overwritten by a save
+        assert!(!storage.path.exists());
+        assert_eq!(fs::read_dir(&*dir).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn restoring_a_backup_can_be_undone() {
+        let dir = TestDir::new();
+        let mut storage = JsonStorage::new(dir.join("notes.json"), 3);
+        storage.save(&notes(&["old"])).unwrap();
+        storage.save(&notes(&["new"])).unwrap();
+
+        let mut notes = Notes::with_storage(Box::new(storage)).unwrap();
+        notes.restore(1).unwrap();
+        assert_eq!(titles(&notes.map), ["old"]);
+        assert!(notes.undo().unwrap());
+        assert_eq!(titles(&notes.map), ["new"]);
+
+        // the undone restore is what is stored
+        let mut storage = JsonStorage::new(dir.join("notes.json"), 3);
+        assert_eq!(titles(&storage.load().unwrap()), ["new"]);
+        assert!(notes.restore(9).is_err());
+    }
+}
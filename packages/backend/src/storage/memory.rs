@@ -0,0 +1,44 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::Storage;
+use crate::error::Result;
+use crate::note::Note;
+
+/// Notes that only live in memory
+/// Clones share the same notes, so what has been saved can be inspected after the storage has
+/// been handed over to `Notes`
+#[derive(Debug, Default, Clone)]
+pub struct MemoryStorage {
+    /// The saved notes
+    notes: Rc<RefCell<Vec<Note>>>,
+}
+
+impl MemoryStorage {
+    /// Storage that starts out with the notes
+    pub fn new(notes: Vec<Note>) -> Self {
+        Self {
+            notes: Rc::new(RefCell::new(notes)),
+        }
+    }
+
+    /// The notes as they were last saved
+    pub fn notes(&self) -> Vec<Note> {
+        self.notes.borrow().to_owned()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn name(&self) -> String {
+        String::from("memory")
+    }
+
+    fn load(&mut self) -> Result<Vec<Note>> {
+        Ok(self.notes())
+    }
+
+    fn save(&mut self, notes: &[Note]) -> Result<()> {
+        *self.notes.borrow_mut() = notes.to_owned();
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+use std::fmt::Debug;
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::note::Note;
+
+/// Notes stored in a JSON file
+pub mod json;
+
+/// Notes kept in memory, mainly for tests
+pub mod memory;
+
+/// Notes stored in a SQLite database
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Where the notes are stored
+pub trait Storage: Debug {
+    /// Name of the storage as shown to the user, e.g. the notebook
+    fn name(&self) -> String;
+
+    /// Load all the notes, upgraded to the current version of the format
+    fn load(&mut self) -> Result<Vec<Note>>;
+
+    /// Store the notes, replacing all the notes that were stored before
+    fn save(&mut self, notes: &[Note]) -> Result<()>;
+
+    /// Why the notes had to be recovered by the last load, if they had to be
+    fn take_recovery(&mut self) -> Option<Error> {
+        None
+    }
+
+    /// Numbers of the backups that exist, from the most recent to the oldest
+    fn backups(&self) -> Vec<usize> {
+        Vec::new()
+    }
+
+    /// Read the notes of a backup, where 1 is the most recent one
+    fn read_backup(&self, _backup: usize) -> Result<Vec<Note>> {
+        Err(Error::BackupNotFound.into())
+    }
+}
+
+/// Storage for a file, chosen by its extension
+/// `.db`, `.sqlite` and `.sqlite3` files are SQLite databases, all others are JSON files
+pub fn open(path: impl Into<PathBuf>, config: &Config) -> Result<Box<dyn Storage>> {
+    let path = path.into();
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        #[cfg(feature = "sqlite")]
+        "db" | "sqlite" | "sqlite3" => Ok(Box::new(sqlite::SqliteStorage::open(path)?)),
+        #[cfg(not(feature = "sqlite"))]
+        "db" | "sqlite" | "sqlite3" => Err(Error::UnsupportedStorage.into()),
+        _ => Ok(Box::new(json::JsonStorage::new(path, config.backups()))),
+    }
+}
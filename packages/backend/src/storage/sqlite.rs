@@ -0,0 +1,266 @@
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use super::json::StoredNotes;
+use super::Storage;
+use crate::error::Result;
+use crate::migration::{self, CURRENT_VERSION};
+use crate::note::Note;
+
+/// Table of the notes, where `data` holds the complete note as JSON and the other columns are
+/// there to query on
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS notes (
+        id TEXT PRIMARY KEY NOT NULL,
+        position INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        state TEXT NOT NULL,
+        category TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS notes_position ON notes (position);
+";
+
+/// Notes stored in a SQLite database
+/// Only the notes that differ from those in the database are written, which keeps saving
+/// fast for large sets of notes
+#[derive(Debug)]
+pub struct SqliteStorage {
+    /// File path of the database
+    path: PathBuf,
+
+    /// Connection to the database
+    connection: Connection,
+}
+
+impl SqliteStorage {
+    /// Open, or create, the database
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        if let Some(prefix) = path.parent() {
+            // create all the required directories
+            fs::create_dir_all(prefix)?;
+        }
+
+        let connection = Connection::open(&path)?;
+        let exists: bool = connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'notes')",
+            [],
+            |row| row.get(0),
+        )?;
+        connection.execute_batch(SCHEMA)?;
+        if !exists {
+            connection.pragma_update(None, "user_version", CURRENT_VERSION)?;
+        }
+
+        Ok(Self { path, connection })
+    }
+}
+
+impl Storage for SqliteStorage {
+    /// Name of the database without extension
+    fn name(&self) -> String {
+        self.path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    fn load(&mut self) -> Result<Vec<Note>> {
+        let version: u64 = self
+            .connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+        let mut statement = self
+            .connection
+            .prepare("SELECT data FROM notes ORDER BY position")?;
+        let notes = statement
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|data| Ok(serde_json::from_str(&data?)?))
+            .collect::<Result<Vec<Value>>>()?;
+        drop(statement);
+
+        // the notes go through the same migrations as those stored in a file
+        let (value, _) = migration::migrate(json!({ "version": version, "notes": notes }))?;
+        let notes = serde_json::from_value::<StoredNotes>(value)?.notes;
+
+        if version < CURRENT_VERSION {
+            self.save(&notes)?;
+            self.connection
+                .pragma_update(None, "user_version", CURRENT_VERSION)?;
+        }
+
+        Ok(notes)
+    }
+
+    /// Save the notes, comparing them with what is in the database within the same transaction,
+    /// so changes that another process made since the notes were loaded are replaced as well
+    fn save(&mut self, notes: &[Note]) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        {
+            let mut select = transaction.prepare_cached("SELECT id, position, data FROM notes")?;
+            let stored = select
+                .query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, (row.get(1)?, row.get(2)?)))
+                })?
+                .collect::<rusqlite::Result<HashMap<String, (usize, String)>>>()?;
+
+            let mut upsert = transaction.prepare_cached(
+                "INSERT OR REPLACE INTO notes (id, position, title, state, category, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            let mut ids = HashSet::with_capacity(notes.len());
+            for (position, note) in notes.iter().enumerate() {
+                let id = note.id.to_string();
+                let data = serde_json::to_string(note)?;
+                let unchanged = stored
+                    .get(&id)
+                    .is_some_and(|(p, d)| *p == position && *d == data);
+                if !unchanged {
+                    upsert.execute(params![
+                        id,
+                        position,
+                        note.title,
+                        String::from(note.state.to_owned()),
+                        note.category,
+                        data
+                    ])?;
+                }
+                ids.insert(id);
+            }
+
+            let mut delete = transaction.prepare_cached("DELETE FROM notes WHERE id = ?1")?;
+            for id in stored.keys().filter(|id| !ids.contains(*id)) {
+                delete.execute(params![id])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::State;
+    use crate::test_dir::TestDir;
+    use std::path::Path;
+
+    /// Notes with the titles
+    fn notes(titles: &[&str]) -> Vec<Note> {
+        titles
+            .iter()
+            .map(|title| Note::new(*title, String::new(), State::Todo))
+            .collect()
+    }
+
+    /// Notes as they are stored in the database when it is opened again
+    fn reload(path: &Path) -> Vec<Note> {
+        SqliteStorage::open(path).unwrap().load().unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = TestDir::new();
+        let path = dir.join("notes.db");
+        let mut storage = SqliteStorage::open(&path).unwrap();
+        assert!(storage.load().unwrap().is_empty());
+        assert_eq!(storage.name(), "notes");
+
+        let mut saved = notes(&["a", "b"]);
+        saved[1].tags = vec![String::from("tag")];
+        saved[1].description = Some(String::from("description"));
+        storage.save(&saved).unwrap();
+        assert_eq!(reload(&path), saved);
+    }
+
+    #[test]
+    fn reorder_and_delete() {
+        let dir = TestDir::new();
+        let path = dir.join("notes.db");
+        let mut storage = SqliteStorage::open(&path).unwrap();
+        let mut saved = notes(&["a", "b", "c"]);
+        storage.save(&saved).unwrap();
+
+        saved.swap(0, 2);
+        storage.save(&saved).unwrap();
+        assert_eq!(reload(&path), saved);
+
+        saved.remove(1);
+        storage.save(&saved).unwrap();
+        assert_eq!(reload(&path), saved);
+    }
+
+    #[test]
+    fn older_versions_are_migrated() {
+        let dir = TestDir::new();
+        let path = dir.join("notes.db");
+        let connection = Connection::open(&path).unwrap();
+        connection.execute_batch(SCHEMA).unwrap();
+        connection.pragma_update(None, "user_version", 2).unwrap();
+        let note = json!({
+            "id": "aaaa0000-0000-4000-8000-000000000001",
+            "title": "title",
+            "state": "Todo",
+            "category": "work",
+            "created_at": "2022-06-01T12:00:00+00:00",
+            "updated_at": "2022-06-01T12:00:00+00:00",
+            "ends_at": null,
+            "tags": [],
+            "description": null,
+        });
+        connection
+            .execute(
+                "INSERT INTO notes (id, position, title, state, category, data)
+                 VALUES (?1, 0, 'title', 'todo', 'work', ?2)",
+                params![note["id"].as_str(), note.to_string()],
+            )
+            .unwrap();
+        drop(connection);
+
+        let loaded = SqliteStorage::open(&path).unwrap().load().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].category, "work");
+        assert!(loaded[0].checklist.is_empty());
+        assert_eq!(loaded[0].parent, None);
+
+        // the upgraded notes are stored in the current version
+        let connection = Connection::open(&path).unwrap();
+        let version: u64 = connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, CURRENT_VERSION);
+        let data: String = connection
+            .query_row("SELECT data FROM notes", [], |row| row.get(0))
+            .unwrap();
+        assert!(data.contains("\"checklist\""));
+    }
+
+    #[test]
+    fn save_replaces_changes_of_another_handle() {
+        let dir = TestDir::new();
+        let path = dir.join("notes.db");
+        let saved = notes(&["a", "b", "c"]);
+        let mut first = SqliteStorage::open(&path).unwrap();
+        first.load().unwrap();
+        first.save(&saved).unwrap();
+
+        // another process, like the CLI while the TUI is open, removes all the notes
+        let mut second = SqliteStorage::open(&path).unwrap();
+        second.load().unwrap();
+        second.save(&[]).unwrap();
+        first.save(&saved).unwrap();
+        assert_eq!(reload(&path), saved);
+
+        // or adds and changes notes
+        let mut changed = saved.to_owned();
+        changed[0].title = String::from("changed");
+        changed.push(Note::new("d", String::new(), State::Todo));
+        second.save(&changed).unwrap();
+        first.save(&saved).unwrap();
+        assert_eq!(reload(&path), saved);
+    }
+}
@@ -0,0 +1,33 @@
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// A new, empty directory in the temporary directory, which is removed when it is dropped
+pub struct TestDir {
+    /// Path of the directory
+    path: PathBuf,
+}
+
+impl TestDir {
+    /// Create the directory
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("vodo-test-{}", Uuid::new_v4().simple()));
+        fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+}
+
+impl Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
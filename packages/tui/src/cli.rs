@@ -0,0 +1,525 @@
+use crate::terminal::editor;
+use backend::config::Config;
+use backend::error::Error;
+use backend::note::{Note, Notes, State, TagMatch};
+use backend::query::Query;
+use chrono::{DateTime, Local, Utc};
+use clap::{Parser, Subcommand};
+use std::io::Write;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Result of a command, errors are reported to the user with a non-zero exit code
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Todo and note manager
+///
+/// Without a command the TUI is started
+#[derive(Parser)]
+#[clap(name = "vodo", version)]
+pub struct Cli {
+    /// Print the output as JSON
+    #[clap(long, global = true)]
+    pub json: bool,
+
+    /// File to store the notes in, instead of a notebook [env: VODO_FILE]
+    #[clap(long, short, global = true)]
+    pub file: Option<PathBuf>,
+
+    /// Notebook to open [env: VODO_NOTEBOOK]
+    #[clap(long, short = 'b', global = true)]
+    pub notebook: Option<String>,
+
+    /// Command to run instead of the TUI
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Commands that operate on the notes without the TUI
+/// A note is referenced by its id, or any unique prefix of it like the short id shown by `vodo list`
+#[derive(Subcommand)]
+pub enum Command {
+    /// Add a new note
+    Add {
+        /// Title of the note
+        title: String,
+
+        /// Category of the note
+        #[clap(long, short, default_value = "")]
+        category: String,
+
+        /// State of the note
+        #[clap(long, short, default_value = "todo")]
+        state: State,
+
+        /// Due date of the note, as `YYYY-MM-DD`
+        #[clap(long, short)]
+        due: Option<String>,
+
+        /// Tag of the note, can be given multiple times
+        #[clap(long = "tag", short)]
+        tags: Vec<String>,
+
+        /// Id of the note to add this note under
+        #[clap(long, short)]
+        parent: Option<String>,
+    },
+
+    /// List the notes
+    #[clap(alias = "ls")]
+    List {
+        /// Only list the notes with this state
+        #[clap(long, short)]
+        state: Option<State>,
+
+        /// Only list the notes in this category
+        #[clap(long, short)]
+        category: Option<String>,
+
+        /// Only list the notes with this tag, can be given multiple times
+        #[clap(long = "tag", short)]
+        tags: Vec<String>,
+
+        /// List the archived notes instead
+        #[clap(long, short)]
+        archived: bool,
+    },
+
+    /// Mark a note as done
+    Done {
+        /// Id of the note
+        id: String,
+    },
+
+    /// Move a note to the archive
+    Archive {
+        /// Id of the note
+        id: String,
+    },
+
+    /// Restore a note from the archive
+    Unarchive {
+        /// Id of the note
+        id: String,
+    },
+
+    /// Remove a note with all of its children
+    #[clap(alias = "delete")]
+    Rm {
+        /// Id of the note
+        id: String,
+    },
+
+    /// List the notebooks
+    Notebooks,
+
+    /// Restore a backup of the notes, without a number the backups are listed
+    Restore {
+        /// Number of the backup, where 1 is the most recent one
+        backup: Option<usize>,
+    },
+
+    /// Edit a note, without any options the note is opened in `$EDITOR`
+    Edit {
+        /// Id of the note
+        id: String,
+
+        /// New title of the note
+        #[clap(long)]
+        title: Option<String>,
+
+        /// New category of the note
+        #[clap(long, short)]
+        category: Option<String>,
+
+        /// New state of the note
+        #[clap(long, short)]
+        state: Option<State>,
+
+        /// New due date of the note, as `YYYY-MM-DD`, empty to remove it
+        #[clap(long, short)]
+        due: Option<String>,
+
+        /// New tags of the note, replaces all the tags
+        #[clap(long = "tag", short)]
+        tags: Option<Vec<String>>,
+    },
+}
+
+/// Run a command on the notes
+pub fn run(
+    command: Command,
+    mut notes: Notes,
+    config: &Config,
+    json: bool,
+    out: &mut impl Write,
+) -> Result<()> {
+    match command {
+        Command::Add {
+            title,
+            category,
+            state,
+            due,
+            tags,
+            parent,
+        } => {
+            let parent = match parent {
+                Some(parent) => Some(notes.resolve_id(&parent)?),
+                None => None,
+            };
+            let note = Note {
+                ends_at: Note::parse_ends_at(due.as_deref().unwrap_or_default())?,
+                tags,
+                parent,
+                ..Note::new(title, category, state)
+            };
+            let id = note.id;
+            notes.put(note)?;
+            notes.expire_overdue()?;
+            print(&notes, &[id], json, out)
+        }
+        Command::List {
+            state,
+            category,
+            tags,
+            archived,
+        } => {
+            let query = Query {
+                states: state.into_iter().collect(),
+                category,
+                tags,
+                tag_match: TagMatch::All,
+                archived: Some(archived),
+                ..Query::default()
+            };
+            let ids: Vec<Uuid> = notes.query(&query).iter().map(|(_, n)| n.id).collect();
+            print(&notes, &ids, json, out)
+        }
+        Command::Done { id } => {
+            let id = notes.resolve_id(&id)?;
+            let note = notes.get_by_id(id).ok_or(Error::NoteNotFound)?;
+            notes.update_by_id(Note {
+                state: State::Done,
+                updated_at: Utc::now().to_rfc3339(),
+                ..note.to_owned()
+            })?;
+            print(&notes, &[id], json, out)
+        }
+        Command::Archive { id } => {
+            let id = notes.resolve_id(&id)?;
+            notes.archive(notes.position(id).ok_or(Error::NoteNotFound)?)?;
+            print(&notes, &[id], json, out)
+        }
+        Command::Unarchive { id } => {
+            let id = notes.resolve_id(&id)?;
+            notes.unarchive(notes.position(id).ok_or(Error::NoteNotFound)?)?;
+            print(&notes, &[id], json, out)
+        }
+        Command::Rm { id } => {
+            let id = notes.resolve_id(&id)?;
+            if json {
+                print(&notes, &[id], json, out)?;
+            }
+            notes.delete_subtree(notes.position(id).ok_or(Error::NoteNotFound)?)
+        }
+        Command::Notebooks => {
+            let notebooks = config.notebooks()?;
+            match json {
+                true => writeln!(out, "{}", serde_json::to_string_pretty(&notebooks)?)?,
+                false => {
+                    for notebook in notebooks {
+                        let current = notebook == notes.notebook();
+                        writeln!(out, "{} {}", if current { "*" } else { " " }, notebook)?;
+                    }
+                }
+            }
+            Ok(())
+        }
+        Command::Restore {
+            backup: Some(backup),
+        } => {
+            notes.restore(backup)?;
+            let ids: Vec<Uuid> = notes.map.iter().map(|n| n.id).collect();
+            print(&notes, &ids, json, out)
+        }
+        Command::Restore { backup: None } => {
+            let backups = notes.backups();
+            match json {
+                true => writeln!(out, "{}", serde_json::to_string_pretty(&backups)?)?,
+                false => {
+                    for backup in backups {
+                        writeln!(out, "{}", backup)?;
+                    }
+                }
+            }
+            Ok(())
+        }
+        Command::Edit {
+            id,
+            title,
+            category,
+            state,
+            due,
+            tags,
+        } => {
+            let id = notes.resolve_id(&id)?;
+            let note = notes.get_by_id(id).ok_or(Error::NoteNotFound)?;
+            let edited = match (title, category, state, due, tags) {
+                (None, None, None, None, None) => editor::edit(note)?,
+                (title, category, state, due, tags) => Some(Note {
+                    title: title.unwrap_or_else(|| note.title.to_owned()),
+                    category: category.unwrap_or_else(|| note.category.to_owned()),
+                    state: state.unwrap_or_else(|| note.state.to_owned()),
+                    ends_at: match due {
+                        Some(due) => Note::parse_ends_at(&due)?,
+                        None => note.ends_at.to_owned(),
+                    },
+                    tags: tags.unwrap_or_else(|| note.tags.to_owned()),
+                    updated_at: Utc::now().to_rfc3339(),
+                    ..note.to_owned()
+                }),
+            };
+            if let Some(note) = edited {
+                notes.update_by_id(note)?;
+                notes.expire_overdue()?;
+            }
+            print(&notes, &[id], json, out)
+        }
+    }
+}
+
+/// Print the notes with the ids, either for humans or as JSON
+fn print(notes: &Notes, ids: &[Uuid], json: bool, out: &mut impl Write) -> Result<()> {
+    let selected: Vec<&Note> = ids.iter().filter_map(|id| notes.get_by_id(*id)).collect();
+
+    if json {
+        writeln!(out, "{}", serde_json::to_string_pretty(&selected)?)?;
+        return Ok(());
+    }
+
+    for note in selected {
+        let mut line = format!(
+            "{}  {:<11}  {:<10}  {:<10}  {}",
+            note.short_id(),
+            String::from(note.state.to_owned()),
+            note.category,
+            note.due_date()
+                .map(|d| DateTime::<Local>::from(d).format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+            note.title
+        );
+        if let Some((done, total)) = note.progress() {
+            line.push_str(&format!(" [{}/{}]", done, total));
+        }
+        for tag in &note.tags {
+            line.push_str(&format!(" #{}", tag));
+        }
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::storage::memory::MemoryStorage;
+
+    /// Parse the arguments after `vodo`
+    fn parse(args: &[&str]) -> std::result::Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("vodo").chain(args.iter().copied()))
+    }
+
+    /// Notes with the ids and titles, stored in memory
+    fn memory_notes(notes: &[(&str, &str)]) -> (Notes, MemoryStorage) {
+        let map = notes
+            .iter()
+            .map(|(id, title)| Note {
+                id: Uuid::parse_str(id).unwrap(),
+                ..Note::new(*title, String::new(), State::Todo)
+            })
+            .collect();
+        let storage = MemoryStorage::new(map);
+        let notes = Notes::with_storage(Box::new(storage.clone())).unwrap();
+        (notes, storage)
+    }
+
+    /// Run the command line on the notes, with its output
+    fn run_args(notes: Notes, args: &[&str]) -> Result<String> {
+        let cli = parse(args)?;
+        let mut out = Vec::new();
+        run(
+            cli.command.unwrap(),
+            notes,
+            &Config::default(),
+            cli.json,
+            &mut out,
+        )?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    /// Id of the first note in the tests
+    const A: &str = "aaaa0000-0000-4000-8000-000000000001";
+
+    /// Id of the second note in the tests, which shares its first characters with the first one
+    const B: &str = "aaaa1111-0000-4000-8000-000000000002";
+
+    #[test]
+    fn without_command_the_tui_is_started() {
+        let cli = parse(&[]).unwrap();
+        assert!(cli.command.is_none());
+        assert!(!cli.json);
+    }
+
+    #[test]
+    fn add_arguments() {
+        let cli = parse(&[
+            "add",
+            "title",
+            "-c",
+            "work",
+            "-s",
+            "done",
+            "--due",
+            "2022-06-01",
+            "--tag",
+            "a",
+            "-t",
+            "b",
+            "--json",
+        ])
+        .unwrap();
+        assert!(cli.json);
+        match cli.command {
+            Some(Command::Add {
+                title,
+                category,
+                state,
+                due,
+                tags,
+                parent,
+            }) => {
+                assert_eq!(title, "title");
+                assert_eq!(category, "work");
+                assert_eq!(state, State::Done);
+                assert_eq!(due.as_deref(), Some("2022-06-01"));
+                assert_eq!(tags, ["a", "b"]);
+                assert_eq!(parent, None);
+            }
+            _ => panic!("not parsed as add"),
+        }
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        assert!(parse(&["add"]).is_err());
+        assert!(parse(&["add", "title", "--state", "later"]).is_err());
+        assert!(parse(&["done"]).is_err());
+        assert!(parse(&["restore", "one"]).is_err());
+        assert!(parse(&["frobnicate"]).is_err());
+    }
+
+    #[test]
+    fn aliases_and_global_options() {
+        assert!(matches!(
+            parse(&["ls", "--archived"]).unwrap().command,
+            Some(Command::List { archived: true, .. })
+        ));
+        let cli = parse(&["delete", "abc", "--notebook", "work", "--file", "x.json"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Rm { id }) if id == "abc"));
+        assert_eq!(cli.notebook.as_deref(), Some("work"));
+        assert_eq!(cli.file, Some(PathBuf::from("x.json")));
+    }
+
+    #[test]
+    fn notes_are_found_by_a_unique_prefix() {
+        let (notes, storage) = memory_notes(&[(A, "a"), (B, "b")]);
+        let out = run_args(notes, &["done", "AAAA-1"]).unwrap();
+        assert!(out.starts_with("aaaa1111  Done"));
+        assert_eq!(storage.notes()[1].state, State::Done);
+        assert_eq!(storage.notes()[0].state, State::Todo);
+    }
+
+    #[test]
+    fn ambiguous_and_unknown_ids_are_errors() {
+        let (notes, storage) = memory_notes(&[(A, "a"), (B, "b")]);
+        let error = run_args(notes, &["rm", "aaaa"]).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<Error>(),
+            Some(Error::AmbiguousId)
+        ));
+        let (notes, _) = memory_notes(&[(A, "a")]);
+        let error = run_args(notes, &["archive", "b"]).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<Error>(),
+            Some(Error::NoteNotFound)
+        ));
+        assert_eq!(storage.notes().len(), 2);
+    }
+
+    #[test]
+    fn add_and_list_as_json() {
+        let (notes, storage) = memory_notes(&[]);
+        let out = run_args(notes, &["add", "title", "-c", "work", "--json"]).unwrap();
+        let added: Vec<Note> = serde_json::from_str(&out).unwrap();
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].category, "work");
+        assert_eq!(storage.notes(), added);
+
+        let notes = Notes::with_storage(Box::new(storage.clone())).unwrap();
+        let out = run_args(notes, &["list", "--category", "home", "--json"]).unwrap();
+        assert_eq!(out.trim(), "[]");
+        assert!(run_args(memory_notes(&[]).0, &["add", "title", "--due", "June"]).is_err());
+    }
+
+    #[test]
+    fn rm_removes_the_children() {
+        let (notes, storage) = memory_notes(&[(A, "a")]);
+        run_args(notes, &["add", "child", "--parent", "aaaa0"]).unwrap();
+        assert_eq!(storage.notes()[1].parent, Some(Uuid::parse_str(A).unwrap()));
+
+        let notes = Notes::with_storage(Box::new(storage.clone())).unwrap();
+        run_args(notes, &["rm", A]).unwrap();
+        assert!(storage.notes().is_empty());
+    }
+
+    #[test]
+    fn archive_and_unarchive() {
+        let (notes, storage) = memory_notes(&[(A, "a")]);
+        run_args(notes, &["archive", "aaaa"]).unwrap();
+        assert!(storage.notes()[0].archived_at.is_some());
+
+        let notes = Notes::with_storage(Box::new(storage.clone())).unwrap();
+        assert_eq!(run_args(notes, &["list"]).unwrap(), "");
+        let notes = Notes::with_storage(Box::new(storage.clone())).unwrap();
+        assert!(run_args(notes, &["list", "-a"]).unwrap().contains(" a"));
+
+        let notes = Notes::with_storage(Box::new(storage.clone())).unwrap();
+        run_args(notes, &["unarchive", "aaaa"]).unwrap();
+        assert!(storage.notes()[0].archived_at.is_none());
+    }
+
+    #[test]
+    fn edit_with_options_keeps_the_rest() {
+        let (notes, storage) = memory_notes(&[(A, "a")]);
+        run_args(notes, &["edit", "aaaa", "--title", "b", "--tag", "x"]).unwrap();
+        let note = &storage.notes()[0];
+        assert_eq!(note.title, "b");
+        assert_eq!(note.tags, ["x"]);
+        assert_eq!(note.state, State::Todo);
+    }
+
+    #[test]
+    fn restore_without_backups() {
+        let (notes, _) = memory_notes(&[(A, "a")]);
+        assert_eq!(
+            run_args(notes, &["restore", "--json"]).unwrap().trim(),
+            "[]"
+        );
+        let (notes, _) = memory_notes(&[(A, "a")]);
+        let error = run_args(notes, &["restore", "1"]).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<Error>(),
+            Some(Error::BackupNotFound)
+        ));
+    }
+}
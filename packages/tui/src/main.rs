@@ -4,21 +4,66 @@
 
 #![deny(clippy::suspicious, clippy::complexity)]
 
+use backend::config::Config;
 use backend::note::Notes;
+use clap::Parser;
+use cli::Cli;
 use std::io;
+use std::process::ExitCode;
+use terminal::app::{App, Popup};
 use terminal::frontend::VodoTerminal;
+use terminal::keymap::Keymap;
+use terminal::theme::Theme;
+
+/// Module for the non-interactive commands
+mod cli;
 
 /// Module for terminal buildup and destruction
 mod terminal;
 
-/// Entrypoint for the TUI of `vodo`
-fn main() -> Result<(), io::Error> {
-    let notes = match Notes::new() {
-        Ok(notes) => notes,
-        Err(e) => panic!("{}", e),
-    };
+/// Entrypoint for the TUI and commands of `vodo`
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = Config::load().and_then(|config| {
+        let path = config.notes_path(cli.file, cli.notebook)?;
+        match cli.command {
+            Some(command) => {
+                let mut notes = Notes::open(path, &config)?;
+                if let Some(recovery) = notes.recovery.take() {
+                    eprintln!("vodo: warning: {}", recovery);
+                }
+                // write instead of print, so a closed pipe is an error instead of a panic
+                cli::run(command, notes, &config, cli.json, &mut io::stdout().lock())
+            }
+            None => {
+                // invalid keys and themes are reported before the terminal is taken over
+                let keymap = Keymap::new(&config.keys)?;
+                let theme = Theme::new(&config)?;
+                let (notes, popup) = App::open_notes(&path, &config)?;
+                run_tui(notes, config, keymap, theme, popup).map_err(Into::into)
+            }
+        }
+    });
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("vodo: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    let mut terminal = VodoTerminal::setup(notes)?;
+/// Run the TUI until the user quits
+fn run_tui(
+    notes: Notes,
+    config: Config,
+    keymap: Keymap,
+    theme: Theme,
+    popup: Option<Popup>,
+) -> Result<(), io::Error> {
+    let mut terminal = VodoTerminal::setup(notes, config, keymap, theme, popup)?;
 
     terminal.run_app()?;
 
@@ -1,103 +1,663 @@
-use backend::note::{Note, Notes, State};
+use backend::config::Config;
+use backend::error::{Error, Result};
+use backend::note::{ChecklistItem, Note, Notes, State, TagMatch, TreeRow};
+use backend::query::{Query, Sort, SortKey};
 use chrono::Utc;
-use tui::widgets::TableState;
+use crossterm::event::{KeyCode, KeyEvent};
+use std::collections::HashSet;
+use std::path::Path;
+use tui::widgets::{ListState, TableState};
+use uuid::Uuid;
+
+use super::form::{FormField, NoteForm};
+use super::input::LineEditor;
+use super::keymap::Keymap;
+use super::theme::Theme;
 
 pub struct App {
     pub state: TableState,
     pub notes: Notes,
     pub note_state: NoteState,
+    pub filter: Query,
+    pub show_detail: bool,
+    pub show_archive: bool,
+    pub show_checklist: bool,
+    pub checklist: ListState,
+    pub collapsed: HashSet<Uuid>,
+    pub search: LineEditor,
+    pub sort: Sort,
+    pub group_by_category: bool,
+    pub collapsed_groups: HashSet<String>,
+    pub show_board: bool,
+    pub show_categories: bool,
+    pub category_list: ListState,
+    pub board: BoardState,
+    pub config: Config,
+    pub keymap: Keymap,
+    pub theme: Theme,
+    pub popup: Option<Popup>,
 }
 
 pub enum NoteInputState {
     None,
     New,
     Editting,
-    Category,
+    TagFilter,
+    Notebook,
+    ChecklistItem,
+    Search,
+    RenameCategory,
 }
 
 pub struct NoteState {
     pub input_state: NoteInputState,
     pub show_input_note: bool,
-    pub input: String,
-    pub category: String,
+    pub input: LineEditor,
+    pub tags: LineEditor,
+    pub form: NoteForm,
     pub should_delete: bool,
 }
 
+/// Message that is shown on top of everything until any key is pressed
+pub struct Popup {
+    pub kind: PopupKind,
+    pub message: String,
+}
+
+pub enum PopupKind {
+    Warning,
+    Error,
+}
+
+/// A row of the notes table
+pub enum ViewRow {
+    /// Header of the notes in a category, when the notes are grouped by category
+    Group {
+        category: String,
+        count: usize,
+        collapsed: bool,
+    },
+    /// A note in the tree of notes
+    Note(TreeRow),
+}
+
+/// States of the columns of the board, from left to right
+pub const BOARD_STATES: [State; 5] = [
+    State::None,
+    State::Todo,
+    State::InProgress,
+    State::Done,
+    State::Expired,
+];
+
+/// Selected card of the board
+#[derive(Default)]
+pub struct BoardState {
+    pub column: usize,
+    pub row: usize,
+}
+
+/// States the notes can be narrowed down to, switched through in this order
+pub const STATE_FILTERS: [&[State]; 6] = [
+    &[],
+    &[State::None, State::Todo, State::InProgress, State::Expired],
+    &[State::Todo],
+    &[State::InProgress],
+    &[State::Done],
+    &[State::Expired],
+];
+
 impl App {
-    pub fn new(items: Notes) -> Self {
+    pub fn new(
+        items: Notes,
+        config: Config,
+        keymap: Keymap,
+        theme: Theme,
+        popup: Option<Popup>,
+    ) -> Self {
         Self {
             state: TableState::default(),
             notes: items,
             note_state: NoteState {
                 input_state: NoteInputState::None,
                 show_input_note: false,
-                input: String::default(),
-                category: String::default(),
+                input: LineEditor::default(),
+                tags: LineEditor::default(),
+                form: NoteForm::default(),
                 should_delete: false,
             },
+            filter: Query::default(),
+            show_detail: false,
+            show_archive: false,
+            show_checklist: false,
+            checklist: ListState::default(),
+            collapsed: HashSet::new(),
+            search: LineEditor::default(),
+            sort: Sort::default(),
+            group_by_category: false,
+            collapsed_groups: HashSet::new(),
+            show_board: false,
+            show_categories: false,
+            category_list: ListState::default(),
+            board: BoardState::default(),
+            config,
+            keymap,
+            theme,
+            popup,
         }
     }
 
-    /// Prepare UI to add a note
-    pub fn prepare_add_note(&mut self) {
-        self.note_state.input_state = NoteInputState::New;
-        self.note_state.show_input_note = true;
+    /// Load the notes from a file, with a popup explaining what happened when they were corrupt
+    /// Corrupt notes are moved aside while loading, so loading them again starts afresh
+    pub fn open_notes(path: &Path, config: &Config) -> Result<(Notes, Option<Popup>)> {
+        match Notes::open(path, config) {
+            Ok(mut notes) => {
+                let popup = notes.recovery.take().map(|e| Popup {
+                    kind: PopupKind::Warning,
+                    message: e.to_string(),
+                });
+                Ok((notes, popup))
+            }
+            Err(e) => match e.downcast_ref::<Error>() {
+                Some(Error::CorruptStorage { .. }) => {
+                    let popup = Popup {
+                        kind: PopupKind::Error,
+                        message: format!("{}, starting with no notes", e),
+                    };
+                    Ok((Notes::open(path, config)?, Some(popup)))
+                }
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Show why a change could not be saved, if it could not
+    /// The change is kept, so it is saved together with the next change
+    pub fn show_unsaved(&mut self, saved: Result<()>) {
+        if let Err(e) = saved {
+            self.popup = Some(Popup {
+                kind: PopupKind::Error,
+                message: format!("{}, the change is kept and saved with the next change", e),
+            });
+        }
     }
 
-    /// Continue to the next step
-    pub fn add_note(&mut self) {
-        self.prepare_set_category();
+    /// Close the popup
+    pub fn dismiss_popup(&mut self) {
+        self.popup = None;
     }
 
-    /// Prepare to set the category
-    pub fn prepare_set_category(&mut self) {
-        self.note_state.input_state = NoteInputState::Category;
+    /// Prepare UI to add a note
+    pub fn prepare_add_note(&mut self) {
+        self.note_state.input_state = NoteInputState::New;
         self.note_state.show_input_note = true;
+        self.note_state.form = NoteForm::new();
+        self.note_state.form.categories = self.notes.categories();
     }
 
-    /// add note
-    pub fn set_category(&mut self) {
-        let note = Note::new(
-            self.note_state.input.to_owned(),
-            self.note_state.category.to_owned(),
-            State::Todo,
-        );
-        self.notes.put(note).unwrap();
-        self.reset();
+    /// Split the input on whitespace and commas into unique tags
+    pub fn parse_tags(input: &str) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in input.split(|c: char| c == ',' || c.is_whitespace()) {
+            if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+                tags.push(tag.to_owned());
+            }
+        }
+        tags
     }
 
     /// Prepare UI to edit the note
     pub fn prepare_edit_note(&mut self) {
-        let idx = self.state.selected().unwrap_or_default();
-        let note = self.notes.get(idx);
+        let note = self.selected().and_then(|idx| self.notes.get(idx));
         if let Some(note) = note {
             self.note_state.input_state = NoteInputState::Editting;
             self.note_state.show_input_note = true;
-            self.note_state.input = note.title.to_owned();
+            self.note_state.form = NoteForm::edit(note);
+            self.note_state.form.categories = self.notes.categories();
         } else {
             self.note_state.show_input_note = false;
         }
     }
 
-    /// Edit and update the note
-    pub fn edit_note(&mut self) {
-        let idx = self.state.selected().unwrap_or_default();
-        let note = self.notes.get(idx);
-        if let Some(note) = note {
-            let mut n = Note {
-                title: self.note_state.input.to_owned(),
-                ..note.to_owned()
-            };
-            self.notes.update(&mut n, idx).unwrap();
+    /// Add or update the note of the form, or cancel when cancel has the focus
+    /// An invalid form stays open, showing what is wrong
+    pub fn save_note(&mut self) -> Result<()> {
+        let form = &mut self.note_state.form;
+        if form.focus == FormField::Cancel {
+            self.reset();
+            return Ok(());
         }
+        let idx = form
+            .id
+            .and_then(|id| self.notes.map.iter().position(|n| n.id == id));
+        let note = match idx {
+            Some(idx) => self.notes.map[idx].to_owned(),
+            None => Note::new("", String::new(), State::Todo),
+        };
+        match form.apply(note) {
+            Ok(mut note) => {
+                let saved = match idx {
+                    Some(idx) => self.notes.update(&mut note, idx),
+                    None => self.notes.put(note),
+                };
+                // the note is kept when it could not be saved, so the form is closed either way
+                self.reset();
+                saved?;
+                self.notes.expire_overdue()?;
+            }
+            Err(e) => form.error = Some(e),
+        }
+        Ok(())
+    }
+
+    /// Prepare UI to search the notes as you type
+    pub fn prepare_search(&mut self) {
+        self.note_state.input_state = NoteInputState::Search;
+        self.note_state.show_input_note = true;
+    }
+
+    /// Edit the input that is shown with a key press
+    /// Changing the search selects the best match
+    pub fn edit_input(&mut self, key: KeyEvent) {
+        let search = self.search.value().to_owned();
+        match self.note_state.input_state {
+            NoteInputState::New | NoteInputState::Editting => {
+                let action = self.keymap.input_action(key);
+                self.note_state.form.handle(key, action);
+            }
+            NoteInputState::RenameCategory if key.code == KeyCode::Tab => {
+                let categories = self.notes.categories();
+                self.note_state.input.complete(&categories);
+            }
+            _ => {
+                if let Some(editor) = self.editor() {
+                    editor.handle(key);
+                }
+            }
+        }
+        if self.search.value() != search {
+            self.state.select(Some(0));
+        }
+    }
+
+    /// Line editor of the input that is shown
+    pub fn editor(&mut self) -> Option<&mut LineEditor> {
+        match self.note_state.input_state {
+            NoteInputState::New | NoteInputState::Editting => self.note_state.form.editor(),
+            NoteInputState::Notebook
+            | NoteInputState::ChecklistItem
+            | NoteInputState::RenameCategory => Some(&mut self.note_state.input),
+            NoteInputState::TagFilter => Some(&mut self.note_state.tags),
+            NoteInputState::Search => Some(&mut self.search),
+            NoteInputState::None => None,
+        }
+    }
+
+    /// Continue with what was typed in, according to the input mode
+    pub fn submit_input(&mut self) -> Result<()> {
+        match self.note_state.input_state {
+            NoteInputState::New | NoteInputState::Editting => return self.save_note(),
+            NoteInputState::TagFilter => self.filter_tags(),
+            NoteInputState::Notebook => self.open_notebook(),
+            NoteInputState::ChecklistItem => return self.add_item(),
+            NoteInputState::Search => self.finish_search(),
+            NoteInputState::RenameCategory => return self.rename_category(),
+            NoteInputState::None => {}
+        }
+        Ok(())
+    }
+
+    /// Stop typing, but keep the notes narrowed down to the search
+    pub fn finish_search(&mut self) {
         self.reset();
     }
 
+    /// Stop searching and show all the notes again, keeping the selected note selected
+    pub fn clear_search(&mut self) {
+        let selected = self.selected().map(|idx| self.notes.map[idx].id);
+        self.search.clear();
+        self.reset();
+        if let Some(id) = selected {
+            self.select_note(id);
+        }
+    }
+
+    /// Prepare UI to filter the notes on tags
+    pub fn prepare_filter_tags(&mut self) {
+        self.note_state.input_state = NoteInputState::TagFilter;
+        self.note_state.show_input_note = true;
+        self.note_state.tags = LineEditor::from(self.filter.tags.join(" ").as_str());
+    }
+
+    /// Only show the notes with the entered tags
+    pub fn filter_tags(&mut self) {
+        self.filter.tags = App::parse_tags(self.note_state.tags.value());
+        self.state.select(None);
+        self.reset();
+    }
+
+    /// Switch between matching any or all of the filtered tags
+    pub fn toggle_tag_match(&mut self) {
+        self.filter.tag_match = match self.filter.tag_match {
+            TagMatch::Any => TagMatch::All,
+            TagMatch::All => TagMatch::Any,
+        };
+        self.state.select(None);
+    }
+
+    /// Switch to the next set of states the notes are narrowed down to
+    pub fn next_state_filter(&mut self) {
+        let current = STATE_FILTERS
+            .iter()
+            .position(|states| *states == self.filter.states.as_slice());
+        let next = current.map_or(0, |i| (i + 1) % STATE_FILTERS.len());
+        self.filter.states = STATE_FILTERS[next].to_vec();
+        self.state.select(None);
+    }
+
+    /// Switch to the next category the notes are narrowed down to, after the last one all the
+    /// categories are shown again
+    pub fn next_category_filter(&mut self) {
+        let categories = self.notes.categories();
+        let next = match &self.filter.category {
+            Some(current) => categories
+                .iter()
+                .position(|c| c == current)
+                .and_then(|i| categories.get(i + 1)),
+            None => categories.first(),
+        };
+        self.filter.category = next.cloned();
+        self.state.select(None);
+    }
+
+    /// Prepare UI to open a notebook by name
+    pub fn prepare_open_notebook(&mut self) {
+        self.note_state.input_state = NoteInputState::Notebook;
+        self.note_state.show_input_note = true;
+    }
+
+    /// Open the entered notebook, which is created when it does not exist yet
+    /// An invalid name keeps the input open
+    pub fn open_notebook(&mut self) {
+        let name = self.note_state.input.value().trim().to_owned();
+        if self.switch_notebook(&name).is_ok() {
+            self.reset();
+        }
+    }
+
+    /// Open the notebook that comes after the current one
+    pub fn next_notebook(&mut self) {
+        let notebooks = self.config.notebooks().unwrap_or_default();
+        let current = notebooks.iter().position(|n| *n == self.notes.notebook());
+        let next = match current {
+            Some(i) => notebooks.get((i + 1) % notebooks.len()),
+            None => notebooks.first(),
+        };
+        if let Some(next) = next.cloned() {
+            if let Err(e) = self.switch_notebook(&next) {
+                self.popup = Some(Popup {
+                    kind: PopupKind::Error,
+                    message: e.to_string(),
+                });
+            }
+        }
+    }
+
+    /// Replace the notes with those of another notebook
+    fn switch_notebook(&mut self, name: &str) -> Result<()> {
+        let (notes, popup) = App::open_notes(&self.config.notebook_path(name)?, &self.config)?;
+        self.notes = notes;
+        self.popup = popup;
+        self.filter = Query::default();
+        self.note_state.should_delete = false;
+        self.state.select(None);
+        Ok(())
+    }
+
+    /// Open or close the detail pane of the selected note
+    pub fn toggle_detail(&mut self) {
+        self.show_detail = !self.show_detail;
+    }
+
+    /// Indices into the notes of all the rows that are shown, in order, where group headers
+    /// have no index
+    pub fn visible(&self) -> Vec<Option<usize>> {
+        self.rows()
+            .into_iter()
+            .map(|row| match row {
+                ViewRow::Note(row) => Some(row.idx),
+                ViewRow::Group { .. } => None,
+            })
+            .collect()
+    }
+
+    /// Rows of the table that are shown, in the order they are shown
+    /// When the notes are grouped, every category gets a header followed by its tree of notes
+    pub fn rows(&self) -> Vec<ViewRow> {
+        let indexes = self.filtered();
+        if !self.group_by_category {
+            let tree = self.notes.tree(&indexes, &self.collapsed);
+            return tree.into_iter().map(ViewRow::Note).collect();
+        }
+
+        let mut categories: Vec<&str> = indexes
+            .iter()
+            .map(|idx| self.notes.map[*idx].category.as_str())
+            .collect();
+        // notes without a category come last
+        categories.sort_by_key(|c| (c.is_empty(), *c));
+        categories.dedup();
+        let mut rows = Vec::new();
+        for category in categories {
+            let group: Vec<usize> = indexes
+                .iter()
+                .copied()
+                .filter(|idx| self.notes.map[*idx].category == category)
+                .collect();
+            let collapsed = self.collapsed_groups.contains(category);
+            rows.push(ViewRow::Group {
+                category: category.to_owned(),
+                count: group.len(),
+                collapsed,
+            });
+            if !collapsed {
+                let tree = self.notes.tree(&group, &self.collapsed);
+                rows.extend(tree.into_iter().map(ViewRow::Note));
+            }
+        }
+        rows
+    }
+
+    /// Indexes of the notes that match the filters and search, sorted
+    fn filtered(&self) -> Vec<usize> {
+        let query = Query {
+            archived: Some(self.show_archive),
+            search: self.search.value().to_owned(),
+            ..self.filter.to_owned()
+        };
+        let mut indexes: Vec<usize> = self
+            .notes
+            .query(&query)
+            .into_iter()
+            .map(|(idx, _)| idx)
+            .collect();
+        self.sort.sort(&mut indexes, &self.notes.map);
+        indexes
+    }
+
+    /// Select the row of a note, if it is shown
+    fn select_note(&mut self, id: Uuid) {
+        let row = self
+            .visible()
+            .iter()
+            .position(|idx| idx.is_some_and(|idx| self.notes.map[idx].id == id));
+        if row.is_some() {
+            self.state.select(row);
+        }
+    }
+
+    /// Index of the sibling before, or after, the selected note in the tree, within its group
+    fn sibling(&self, before: bool) -> Option<usize> {
+        let rows = self.rows();
+        let i = self.state.selected().filter(|i| *i < rows.len())?;
+        let depth = match &rows[i] {
+            ViewRow::Note(row) => row.depth,
+            ViewRow::Group { .. } => return None,
+        };
+        let mut candidates: Box<dyn Iterator<Item = &ViewRow>> = match before {
+            true => Box::new(rows[..i].iter().rev()),
+            false => Box::new(rows[i + 1..].iter()),
+        };
+        let found = candidates.find(|row| match row {
+            ViewRow::Note(row) => row.depth <= depth,
+            ViewRow::Group { .. } => true,
+        });
+        match found {
+            Some(ViewRow::Note(row)) if row.depth == depth => Some(row.idx),
+            _ => None,
+        }
+    }
+
+    /// Category of the group before, or after, the group of the selected note
+    fn adjacent_group(&self, before: bool) -> Option<String> {
+        let idx = self.selected()?;
+        let categories: Vec<String> = self
+            .rows()
+            .into_iter()
+            .filter_map(|row| match row {
+                ViewRow::Group { category, .. } => Some(category),
+                ViewRow::Note(_) => None,
+            })
+            .collect();
+        let i = categories
+            .iter()
+            .position(|c| *c == self.notes.map[idx].category)?;
+        match before {
+            true => i.checked_sub(1).and_then(|i| categories.get(i)).cloned(),
+            false => categories.get(i + 1).cloned(),
+        }
+    }
+
+    /// Index into the notes of the selected row, if it is a note
+    pub fn selected(&self) -> Option<usize> {
+        if self.show_board {
+            return self
+                .board_cards(self.board.column)
+                .get(self.board.row)
+                .copied();
+        }
+        let row = self.state.selected()?;
+        self.visible().get(row).copied().flatten()
+    }
+
+    /// Indexes of the notes in a column of the board, in the order they are shown
+    pub fn board_cards(&self, column: usize) -> Vec<usize> {
+        self.filtered()
+            .into_iter()
+            .filter(|idx| self.notes.map[*idx].state == BOARD_STATES[column])
+            .collect()
+    }
+
+    /// Switch between the table and the board
+    pub fn toggle_board(&mut self) {
+        let selected = self.selected();
+        self.show_board = !self.show_board;
+        self.note_state.should_delete = false;
+        match (self.show_board, selected) {
+            (true, Some(idx)) => self.select_card(idx),
+            (false, Some(idx)) => self.select_note(self.notes.map[idx].id),
+            (true, None) => {
+                let column = (0..BOARD_STATES.len()).find(|c| !self.board_cards(*c).is_empty());
+                self.board = BoardState {
+                    column: column.unwrap_or(0),
+                    row: 0,
+                };
+            }
+            (false, None) => {}
+        }
+    }
+
+    /// Select the card of a note on the board, if it is shown
+    fn select_card(&mut self, idx: usize) {
+        let state = &self.notes.map[idx].state;
+        if let Some(column) = BOARD_STATES.iter().position(|s| s == state) {
+            if let Some(row) = self.board_cards(column).iter().position(|i| *i == idx) {
+                self.board = BoardState { column, row };
+            }
+        }
+    }
+
+    /// Select the card below, or above, in the same column
+    pub fn next_card(&mut self, down: bool) {
+        let len = self.board_cards(self.board.column).len();
+        self.board.row = match (down, self.board.row) {
+            _ if len == 0 => 0,
+            (true, row) if row + 1 >= len => 0,
+            (true, row) => row + 1,
+            (false, 0) => len - 1,
+            (false, row) => row.min(len) - 1,
+        };
+    }
+
+    /// Select the column to the right, or left, keeping the row when possible
+    pub fn next_column(&mut self, right: bool) {
+        self.board.column = match right {
+            true => (self.board.column + 1).min(BOARD_STATES.len() - 1),
+            false => self.board.column.saturating_sub(1),
+        };
+        let len = self.board_cards(self.board.column).len();
+        self.board.row = self.board.row.min(len.saturating_sub(1));
+    }
+
+    /// Move the selected card to the column to the right, or left, which changes its state
+    /// The card stays selected, unless the filters hide it in its new column
+    pub fn move_card(&mut self, right: bool) -> Result<()> {
+        let column = match right {
+            true => self.board.column + 1,
+            false => match self.board.column.checked_sub(1) {
+                Some(column) => column,
+                None => return Ok(()),
+            },
+        };
+        if let (Some(idx), Some(state)) = (self.selected(), BOARD_STATES.get(column)) {
+            let mut note = Note {
+                state: state.to_owned(),
+                updated_at: Utc::now().to_rfc3339(),
+                ..self.notes.map[idx].to_owned()
+            };
+            let saved = self.notes.update(&mut note, idx);
+            self.select_card(idx);
+            self.clamp_selection();
+            return saved;
+        }
+        Ok(())
+    }
+
+    /// Category of the selected row, if it is a group header
+    fn selected_group(&self) -> Option<String> {
+        let row = self.state.selected()?;
+        match self.rows().into_iter().nth(row)? {
+            ViewRow::Group { category, .. } => Some(category),
+            ViewRow::Note(_) => None,
+        }
+    }
+
+    /// Switch between the tree of notes and the notes grouped by category
+    pub fn toggle_group_by_category(&mut self) {
+        let selected = self.selected().map(|idx| self.notes.map[idx].id);
+        self.group_by_category = !self.group_by_category;
+        self.state.select(Some(0));
+        if let Some(id) = selected {
+            self.select_note(id);
+        }
+    }
+
     /// reset the state of the application
     pub fn reset(&mut self) {
         self.note_state.show_input_note = false;
-        self.note_state.category = String::from("");
-        self.note_state.input = String::from("");
+        self.note_state.tags.clear();
+        self.note_state.form = NoteForm::default();
+        self.note_state.input.clear();
         self.note_state.input_state = NoteInputState::None;
     }
 
@@ -107,15 +667,36 @@ impl App {
         match mode {
             NoteInputState::Editting => self.prepare_edit_note(),
             NoteInputState::New => self.prepare_add_note(),
+            NoteInputState::TagFilter => self.prepare_filter_tags(),
+            NoteInputState::Notebook => self.prepare_open_notebook(),
+            NoteInputState::ChecklistItem => self.prepare_add_item(),
+            NoteInputState::Search => self.prepare_search(),
+            NoteInputState::RenameCategory => self.prepare_rename_category(),
             _ => panic!("Unknown note state"),
         };
     }
 
+    /// Periodic work that is done on every tick of the UI
+    pub fn on_tick(&mut self) -> Result<()> {
+        let expired = self.notes.expire_overdue();
+        let archived = match self.config.archive_after() {
+            Some(after) => self.notes.archive_finished(after),
+            None => Ok(false),
+        };
+        // the notes are changed even when they could not be saved
+        if !matches!((&expired, &archived), (Ok(false), Ok(false))) {
+            self.clamp_selection();
+        }
+        expired?;
+        archived?;
+        Ok(())
+    }
+
     /// Select the next note
     pub fn next(&mut self) {
         let i = match self.state.selected() {
             Some(i) => {
-                if i as isize >= (self.notes.map.len() as isize) - 1 {
+                if i as isize >= (self.visible().len() as isize) - 1 {
                     0
                 } else {
                     (i + 1) as isize
@@ -132,7 +713,7 @@ impl App {
         let i = match self.state.selected() {
             Some(i) => {
                 if i == 0 {
-                    self.notes.map.len() as isize - 1
+                    self.visible().len() as isize - 1
                 } else {
                     (i - 1) as isize
                 }
@@ -145,27 +726,29 @@ impl App {
 
     /// Delete the selected note
     /// delete has to be called twice
-    pub fn delete(&mut self) {
+    pub fn delete(&mut self) -> Result<()> {
         if self.note_state.should_delete {
-            if let Some(i) = self.state.selected() {
-                if self.notes.map.get(i).is_some() {
-                    self.notes.delete(i).unwrap();
+            if let (Some(i), Some(idx)) = (self.state.selected(), self.selected()) {
+                if self.notes.map.get(idx).is_some() {
+                    let saved = self.notes.delete_subtree(idx);
                     if i == 0 {
                         self.state.select(Some(0));
                     } else {
                         self.state.select(Some(i - 1));
                     }
                     self.note_state.should_delete = false;
+                    return saved;
                 }
             }
         } else {
             self.note_state.should_delete = true;
         }
+        Ok(())
     }
 
     /// Update the state of a todo with a loop
-    pub fn update_state(&mut self) {
-        if let Some(i) = self.state.selected() {
+    pub fn update_state(&mut self) -> Result<()> {
+        if let Some(i) = self.selected() {
             if self.notes.map.get(i).is_some() {
                 // TODO: could increment be implemented for this?
                 let next_state = match self.notes.map[i].state {
@@ -180,44 +763,738 @@ impl App {
                     updated_at: Utc::now().to_rfc3339(),
                     ..self.notes.map[i].to_owned()
                 };
-                self.notes.update(&mut n, i).unwrap();
+                return self.notes.update(&mut n, i);
             }
         }
+        Ok(())
     }
 
     /// Change the priority of the note to be the first in the list
-    pub fn prioritize(&mut self) {
-        if let Some(i) = self.state.selected() {
-            let note = self.notes.map.remove(i);
-            self.notes.map.insert(0, note);
-            self.notes.save().unwrap();
+    pub fn prioritize(&mut self) -> Result<()> {
+        if !self.is_manual_order() {
+            return Ok(());
+        }
+        match self.selected() {
+            Some(i) => self.notes.move_note(i, 0),
+            None => Ok(()),
         }
     }
 
     /// Change the priority of the note to be the last in the list
-    pub fn deprioritize(&mut self) {
-        if let Some(i) = self.state.selected() {
-            let note = self.notes.map.remove(i);
-            self.notes.map.push(note);
-            self.notes.save().unwrap();
+    pub fn deprioritize(&mut self) -> Result<()> {
+        if !self.is_manual_order() {
+            return Ok(());
+        }
+        match self.selected() {
+            Some(i) => self.notes.move_note(i, self.notes.map.len() - 1),
+            None => Ok(()),
         }
     }
 
     /// Change the priority of the note with the note above
-    pub fn swap_up(&mut self) {
-        if let Some(i) = self.state.selected() {
-            self.notes.map.swap(i, i - 1);
-            self.state.select(Some(i - 1));
-            self.notes.save().unwrap();
+    pub fn swap_up(&mut self) -> Result<()> {
+        self.swap_sibling(true)
+    }
+
+    /// Change the priority of the note with the note below
+    pub fn swap_down(&mut self) -> Result<()> {
+        self.swap_sibling(false)
+    }
+
+    /// Swap the selected note with its sibling before, or after, it
+    /// When there is no sibling in its group, the note is moved to the group before, or after,
+    /// together with its children
+    fn swap_sibling(&mut self, before: bool) -> Result<()> {
+        if !self.is_manual_order() {
+            return Ok(());
+        }
+        let idx = match self.selected() {
+            Some(idx) => idx,
+            None => return Ok(()),
+        };
+        let id = self.notes.map[idx].id;
+        let saved = if let Some(sibling) = self.sibling(before) {
+            self.notes.swap(idx, sibling)
+        } else if let Some(category) = self.adjacent_group(before) {
+            self.notes.set_category(idx, &category)
+        } else {
+            Ok(())
+        };
+        self.select_note(id);
+        saved
+    }
+
+    /// Whether the notes are shown in their manual order, otherwise a warning is shown because
+    /// changes to that order would not be visible
+    fn is_manual_order(&mut self) -> bool {
+        if self.sort.key != SortKey::Manual {
+            self.popup = Some(Popup {
+                kind: PopupKind::Warning,
+                message: String::from(
+                    "Notes can only be reordered in the manual order, press o to switch to it",
+                ),
+            });
+            return false;
         }
+        true
     }
 
-    /// Change the priority of the note with the note above
-    pub fn swap_down(&mut self) {
+    /// Sort the notes on the next column, keeping the selected note selected
+    pub fn next_sort(&mut self) {
+        let selected = self.selected().map(|idx| self.notes.map[idx].id);
+        self.sort.key = self.sort.key.next();
+        if let Some(id) = selected {
+            self.select_note(id);
+        }
+    }
+
+    /// Switch between sorting ascending and descending
+    pub fn toggle_sort_direction(&mut self) {
+        let selected = self.selected().map(|idx| self.notes.map[idx].id);
+        self.sort.descending = !self.sort.descending;
+        if let Some(id) = selected {
+            self.select_note(id);
+        }
+    }
+
+    /// Move the selected note under the sibling before it
+    pub fn indent(&mut self) -> Result<()> {
+        if let (Some(idx), Some(sibling)) = (self.selected(), self.sibling(true)) {
+            let id = self.notes.map[idx].id;
+            let parent = self.notes.map[sibling].id;
+            let saved = self.notes.move_under(idx, Some(parent));
+            self.collapsed.remove(&parent);
+            self.select_note(id);
+            return saved;
+        }
+        Ok(())
+    }
+
+    /// Move the selected note next to its parent
+    pub fn outdent(&mut self) -> Result<()> {
+        if let Some(idx) = self.selected() {
+            let id = self.notes.map[idx].id;
+            if self.notes.map[idx].parent.is_some() {
+                let saved = self.notes.outdent(idx);
+                self.select_note(id);
+                return saved;
+            }
+        }
+        Ok(())
+    }
+
+    /// Hide the children of the selected note, or select its parent when they are hidden
+    /// On a group header the whole group is hidden
+    pub fn collapse(&mut self) {
+        if let Some(category) = self.selected_group() {
+            self.collapsed_groups.insert(category);
+            return;
+        }
+        if let Some(idx) = self.selected() {
+            let note = &self.notes.map[idx];
+            let has_children = !self.notes.children(note.id).is_empty();
+            if has_children && self.collapsed.insert(note.id) {
+                return;
+            }
+            match note.parent {
+                Some(parent) => self.select_note(parent),
+                None => self.select_group(),
+            }
+        }
+    }
+
+    /// Select the header of the group of the selected note
+    fn select_group(&mut self) {
+        if let (Some(row), Some(idx)) = (self.state.selected(), self.selected()) {
+            let category = self.notes.map[idx].category.to_owned();
+            let header = self.rows()[..row]
+                .iter()
+                .rposition(|r| matches!(r, ViewRow::Group { category: c, .. } if *c == category));
+            if header.is_some() {
+                self.state.select(header);
+            }
+        }
+    }
+
+    /// Show the children of the selected note, or the notes of the selected group
+    pub fn expand(&mut self) {
+        if let Some(category) = self.selected_group() {
+            self.collapsed_groups.remove(&category);
+        } else if let Some(idx) = self.selected() {
+            self.collapsed.remove(&self.notes.map[idx].id);
+        }
+    }
+
+    /// Switch between the notes and the archive
+    pub fn toggle_archive(&mut self) {
+        self.show_archive = !self.show_archive;
+        self.note_state.should_delete = false;
+        self.state.select(Some(0));
+    }
+
+    /// Archive the selected note, or restore it when the archive is shown
+    pub fn archive(&mut self) -> Result<()> {
+        if let Some(idx) = self.selected() {
+            let saved = match self.show_archive {
+                true => self.notes.unarchive(idx),
+                false => self.notes.archive(idx),
+            };
+            self.note_state.should_delete = false;
+            self.clamp_selection();
+            return saved;
+        }
+        Ok(())
+    }
+
+    /// Show or hide the checklist of the selected note
+    pub fn toggle_checklist(&mut self) {
+        self.show_checklist = !self.show_checklist && self.selected().is_some();
+        self.note_state.should_delete = false;
+        self.checklist.select(Some(0));
+    }
+
+    /// Change the checklist of the selected note, given the selected item
+    /// The change returns the item that should be selected afterwards
+    fn update_checklist(
+        &mut self,
+        f: impl FnOnce(&mut Vec<ChecklistItem>, usize) -> usize,
+    ) -> Result<()> {
+        let idx = match self.selected() {
+            Some(idx) => idx,
+            None => return Ok(()),
+        };
+        let mut note = self.notes.map[idx].to_owned();
+        let selected = self.checklist.selected().unwrap_or(0);
+        let selected = f(&mut note.checklist, selected);
+        self.checklist.select(Some(selected));
+        if note.checklist == self.notes.map[idx].checklist {
+            return Ok(());
+        }
+        note.updated_at = Utc::now().to_rfc3339();
+        self.notes.update(&mut note, idx)
+    }
+
+    /// Number of items on the checklist of the selected note
+    fn checklist_len(&self) -> usize {
+        self.selected()
+            .and_then(|idx| self.notes.get(idx))
+            .map_or(0, |note| note.checklist.len())
+    }
+
+    /// Select the next checklist item
+    pub fn next_item(&mut self) {
+        let len = self.checklist_len();
+        let i = self.checklist.selected().map_or(0, |i| i + 1);
+        self.checklist.select(Some(if i >= len { 0 } else { i }));
+    }
+
+    /// Select the previous checklist item
+    pub fn previous_item(&mut self) {
+        let len = self.checklist_len();
+        let i = match self.checklist.selected() {
+            Some(i) if i > 0 => i - 1,
+            _ => len.saturating_sub(1),
+        };
+        self.checklist.select(Some(i));
+    }
+
+    /// Prepare UI to add a checklist item
+    pub fn prepare_add_item(&mut self) {
+        self.note_state.input_state = NoteInputState::ChecklistItem;
+        self.note_state.show_input_note = true;
+    }
+
+    /// Add the checklist item below the selected one
+    pub fn add_item(&mut self) -> Result<()> {
+        let item = ChecklistItem::new(self.note_state.input.value().trim());
+        self.reset();
+        if item.title.is_empty() {
+            return Ok(());
+        }
+        self.update_checklist(|checklist, i| {
+            let i = (i + 1).min(checklist.len());
+            checklist.insert(i, item);
+            i
+        })
+    }
+
+    /// Check or uncheck the selected checklist item
+    pub fn toggle_item(&mut self) -> Result<()> {
+        self.update_checklist(|checklist, i| {
+            if let Some(item) = checklist.get_mut(i) {
+                item.done = !item.done;
+            }
+            i
+        })
+    }
+
+    /// Delete the selected checklist item
+    pub fn delete_item(&mut self) -> Result<()> {
+        self.update_checklist(|checklist, i| {
+            if i < checklist.len() {
+                checklist.remove(i);
+            }
+            i.min(checklist.len().saturating_sub(1))
+        })
+    }
+
+    /// Swap the selected checklist item with the one above
+    pub fn swap_item_up(&mut self) -> Result<()> {
+        self.update_checklist(|checklist, i| {
+            if i > 0 && i < checklist.len() {
+                checklist.swap(i, i - 1);
+                i - 1
+            } else {
+                i
+            }
+        })
+    }
+
+    /// Swap the selected checklist item with the one below
+    pub fn swap_item_down(&mut self) -> Result<()> {
+        self.update_checklist(|checklist, i| {
+            if i + 1 < checklist.len() {
+                checklist.swap(i, i + 1);
+                i + 1
+            } else {
+                i
+            }
+        })
+    }
+
+    /// Categories that are used, with the number of notes in them, including archived notes
+    pub fn category_rows(&self) -> Vec<(String, usize)> {
+        self.notes
+            .categories()
+            .into_iter()
+            .map(|category| {
+                let notes = self.notes.map.iter();
+                let count = notes.filter(|n| n.category == category).count();
+                (category, count)
+            })
+            .collect()
+    }
+
+    /// Category that is selected in the category manager
+    pub fn selected_category(&self) -> Option<String> {
+        let categories = self.notes.categories();
+        categories.get(self.category_list.selected()?).cloned()
+    }
+
+    /// Show or hide the category manager
+    pub fn toggle_categories(&mut self) {
+        self.show_categories = !self.show_categories;
+        self.note_state.should_delete = false;
+        let selected = self.filter.category.as_ref().and_then(|current| {
+            let categories = self.notes.categories();
+            categories.iter().position(|c| c == current)
+        });
+        self.category_list.select(Some(selected.unwrap_or(0)));
+    }
+
+    /// Select the next or previous category in the category manager
+    pub fn next_category_row(&mut self, forward: bool) {
+        let len = self.notes.categories().len();
+        let i = match (self.category_list.selected(), forward) {
+            (Some(i), true) if i + 1 < len => i + 1,
+            (Some(i), false) if i > 0 => i - 1,
+            (_, true) => 0,
+            (_, false) => len.saturating_sub(1),
+        };
+        self.category_list.select(Some(i));
+    }
+
+    /// Only show the notes in the selected category and close the category manager
+    pub fn filter_selected_category(&mut self) {
+        if let Some(category) = self.selected_category() {
+            self.filter.category = Some(category);
+            self.state.select(None);
+            self.toggle_categories();
+        }
+    }
+
+    /// Prepare UI to rename the selected category
+    pub fn prepare_rename_category(&mut self) {
+        match self.selected_category() {
+            Some(category) => {
+                self.note_state.input_state = NoteInputState::RenameCategory;
+                self.note_state.show_input_note = true;
+                self.note_state.input = LineEditor::from(category.as_str());
+            }
+            None => self.note_state.show_input_note = false,
+        }
+    }
+
+    /// Rename the selected category to the entered name, merging it into a category that
+    /// already has that name
+    /// An empty name keeps the input open, as it would take the category off all its notes
+    pub fn rename_category(&mut self) -> Result<()> {
+        let to = self.note_state.input.value().trim().to_owned();
+        if to.is_empty() {
+            self.popup = Some(Popup {
+                kind: PopupKind::Warning,
+                message: String::from("The category can not be empty"),
+            });
+            return Ok(());
+        }
+        self.reset();
+        let from = match self.selected_category() {
+            Some(from) => from,
+            None => return Ok(()),
+        };
+        let saved = self.notes.rename_category(&from, &to);
+        if self.filter.category.as_ref() == Some(&from) {
+            self.filter.category = Some(to.to_owned());
+        }
+        let categories = self.notes.categories();
+        let selected = categories.iter().position(|c| *c == to);
+        self.category_list.select(Some(selected.unwrap_or(0)));
+        saved.map(|_| ())
+    }
+
+    /// Undo the most recent change to the notes
+    pub fn undo(&mut self) -> Result<()> {
+        self.note_state.should_delete = false;
+        let undone = self.notes.undo();
+        self.clamp_selection();
+        undone.map(|_| ())
+    }
+
+    /// Redo the most recently undone change to the notes
+    pub fn redo(&mut self) -> Result<()> {
+        self.note_state.should_delete = false;
+        let redone = self.notes.redo();
+        self.clamp_selection();
+        redone.map(|_| ())
+    }
+
+    /// Keep the selection on a visible note after notes have been removed
+    fn clamp_selection(&mut self) {
+        let len = self.visible().len();
         if let Some(i) = self.state.selected() {
-            self.notes.map.swap(i, i + 1);
-            self.state.select(Some(i + 1));
-            self.notes.save().unwrap();
+            if i >= len {
+                self.state.select(Some(len.saturating_sub(1)));
+            }
+        }
+        let len = self.checklist_len();
+        if let Some(i) = self.checklist.selected() {
+            if i >= len {
+                self.checklist.select(Some(len.saturating_sub(1)));
+            }
+        }
+        let len = self.board_cards(self.board.column).len();
+        self.board.row = self.board.row.min(len.saturating_sub(1));
+        let len = self.notes.categories().len();
+        if let Some(i) = self.category_list.selected() {
+            if i >= len {
+                self.category_list.select(Some(len.saturating_sub(1)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::storage::memory::MemoryStorage;
+    use backend::storage::Storage;
+    use std::collections::HashMap;
+
+    /// App over notes in memory, with the titles and categories
+    fn app(notes: &[(&str, &str)]) -> App {
+        let map = notes
+            .iter()
+            .map(|(title, category)| Note::new(*title, category.to_string(), State::Todo))
+            .collect();
+        app_with(Box::new(MemoryStorage::new(map)))
+    }
+
+    /// App over the notes of the storage
+    fn app_with(storage: Box<dyn Storage>) -> App {
+        let notes = Notes::with_storage(storage).unwrap();
+        let keymap = Keymap::new(&HashMap::new()).unwrap();
+        let theme = Theme::builtin("dark").unwrap();
+        App::new(notes, Config::default(), keymap, theme, None)
+    }
+
+    /// Storage that loads one note and can not save, like a file on a full disk
+    #[derive(Debug)]
+    struct FullStorage;
+
+    impl Storage for FullStorage {
+        fn name(&self) -> String {
+            "full".into()
+        }
+
+        fn load(&mut self) -> Result<Vec<Note>> {
+            Ok(vec![Note::new("a", String::new(), State::Todo)])
+        }
+
+        fn save(&mut self, _notes: &[Note]) -> Result<()> {
+            Err(Error::UnableToSaveFile.into())
+        }
+    }
+
+    #[test]
+    fn selection_is_kept_in_the_notes_that_are_left_after_expiring() {
+        let mut app = app(&[("a", "work"), ("b", "work")]);
+        for note in app.notes.map.iter_mut() {
+            note.ends_at = Some("2000-01-01T00:00:00+00:00".into());
+        }
+        app.filter.states = vec![State::Todo];
+        app.state.select(Some(1));
+        app.on_tick().unwrap();
+        assert!(app.visible().is_empty());
+        assert_eq!(app.state.selected(), Some(0));
+    }
+
+    #[test]
+    fn unsaved_changes_are_kept_and_shown() {
+        let mut app = app_with(Box::new(FullStorage));
+        app.state.select(Some(0));
+        let saved = app.update_state();
+        assert!(saved.is_err());
+        assert_eq!(app.notes.map[0].state, State::InProgress);
+        assert!(app.popup.is_none());
+        app.show_unsaved(saved);
+        assert!(matches!(
+            app.popup,
+            Some(Popup {
+                kind: PopupKind::Error,
+                ..
+            })
+        ));
+    }
+
+    /// The rows that are shown, with group headers like `[work] 2` and notes indented by depth
+    fn rows(app: &App) -> Vec<String> {
+        app.rows()
+            .into_iter()
+            .map(|row| match row {
+                ViewRow::Group {
+                    category, count, ..
+                } => format!("[{}] {}", category, count),
+                ViewRow::Note(row) => {
+                    format!("{}{}", "  ".repeat(row.depth), app.notes.map[row.idx].title)
+                }
+            })
+            .collect()
+    }
+
+    /// Select the row of the note with the title
+    fn select(app: &mut App, title: &str) {
+        let idx = app.notes.map.iter().position(|n| n.title == title).unwrap();
+        app.select_note(app.notes.map[idx].id);
+    }
+
+    /// Title of the selected note
+    fn selected_title(app: &App) -> String {
+        app.notes.map[app.selected().unwrap()].title.to_owned()
+    }
+
+    #[test]
+    fn groups_have_a_header_with_their_count() {
+        let mut app = app(&[("a", "work"), ("b", "home"), ("c", ""), ("d", "work")]);
+        app.toggle_group_by_category();
+        assert_eq!(
+            rows(&app),
+            ["[home] 1", "b", "[work] 2", "a", "d", "[] 1", "c"]
+        );
+    }
+
+    #[test]
+    fn groups_collapse_and_expand() {
+        let mut app = app(&[("a", "work"), ("b", "home"), ("d", "work")]);
+        app.toggle_group_by_category();
+        app.state.select(Some(2));
+        app.collapse();
+        assert_eq!(rows(&app), ["[home] 1", "b", "[work] 2"]);
+        app.expand();
+        assert_eq!(rows(&app), ["[home] 1", "b", "[work] 2", "a", "d"]);
+
+        // collapsing a note without children selects the header of its group
+        select(&mut app, "d");
+        app.collapse();
+        assert_eq!(app.state.selected(), Some(2));
+    }
+
+    #[test]
+    fn reordering_stays_within_the_group() {
+        let mut app = app(&[("a", "work"), ("b", "home"), ("d", "work")]);
+        app.toggle_group_by_category();
+        select(&mut app, "d");
+        app.swap_up().unwrap();
+        assert_eq!(rows(&app), ["[home] 1", "b", "[work] 2", "d", "a"]);
+        assert_eq!(selected_title(&app), "d");
+    }
+
+    #[test]
+    fn reordering_past_the_group_moves_to_the_next_group() {
+        let mut app = app(&[("a", "work"), ("b", "home")]);
+        app.toggle_group_by_category();
+        select(&mut app, "b");
+        app.swap_down().unwrap();
+        assert_eq!(rows(&app), ["[work] 2", "a", "b"]);
+        assert_eq!(selected_title(&app), "b");
+        // there is no group after the last one
+        app.swap_down().unwrap();
+        assert_eq!(rows(&app), ["[work] 2", "a", "b"]);
+    }
+
+    #[test]
+    fn children_move_to_the_other_group_with_their_parent() {
+        let mut app = app(&[("a", "work"), ("child", "work"), ("b", "home")]);
+        app.notes.map[1].parent = Some(app.notes.map[0].id);
+        app.toggle_group_by_category();
+        assert_eq!(rows(&app), ["[home] 1", "b", "[work] 2", "a", "  child"]);
+
+        select(&mut app, "a");
+        app.swap_up().unwrap();
+        assert_eq!(rows(&app), ["[home] 3", "a", "  child", "b"]);
+        assert_eq!(selected_title(&app), "a");
+
+        // the move is undone in one step
+        app.undo().unwrap();
+        assert_eq!(rows(&app), ["[home] 1", "b", "[work] 2", "a", "  child"]);
+    }
+
+    /// App on the board, with notes in the states
+    fn board(states: &[(&str, State)]) -> App {
+        let mut app = app(&[]);
+        for (title, state) in states {
+            let note = Note::new(*title, String::new(), state.to_owned());
+            app.notes.put(note).unwrap();
         }
+        app.toggle_board();
+        app
+    }
+
+    /// Titles of the cards in a column of the board
+    fn cards(app: &App, column: usize) -> Vec<String> {
+        app.board_cards(column)
+            .into_iter()
+            .map(|idx| app.notes.map[idx].title.to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn board_starts_at_the_first_card() {
+        let app = board(&[("a", State::InProgress), ("b", State::Done)]);
+        assert_eq!((app.board.column, app.board.row), (2, 0));
+        assert_eq!(selected_title(&app), "a");
+
+        let app = board(&[]);
+        assert_eq!((app.board.column, app.board.row), (0, 0));
+        assert_eq!(app.selected(), None);
+    }
+
+    #[test]
+    fn cards_wrap_within_their_column() {
+        let mut app = board(&[("a", State::Todo), ("b", State::Todo), ("c", State::Todo)]);
+        app.next_card(false);
+        assert_eq!(selected_title(&app), "c");
+        app.next_card(true);
+        assert_eq!(selected_title(&app), "a");
+        app.next_card(true);
+        assert_eq!(selected_title(&app), "b");
+    }
+
+    #[test]
+    fn empty_columns_have_no_selection() {
+        let mut app = board(&[("a", State::Todo), ("b", State::Todo), ("c", State::Done)]);
+        app.next_card(true);
+        app.next_column(true);
+        assert_eq!(app.board.column, 2);
+        assert_eq!(app.selected(), None);
+        app.next_card(true);
+        app.next_card(false);
+        assert_eq!(app.board.row, 0);
+        assert_eq!(app.selected(), None);
+        // moving an empty column does nothing
+        app.move_card(true).unwrap();
+        assert_eq!(cards(&app, 3), ["c"]);
+
+        // the row is kept where the next column has enough cards, and clamped otherwise
+        app.next_column(true);
+        assert_eq!(selected_title(&app), "c");
+        app.next_column(true);
+        app.next_column(true);
+        assert_eq!(app.board.column, BOARD_STATES.len() - 1);
+        app.next_column(false);
+        app.next_column(false);
+        app.next_column(false);
+        assert_eq!(selected_title(&app), "a");
+    }
+
+    #[test]
+    fn moved_cards_stay_selected() {
+        let mut app = board(&[("a", State::None), ("b", State::Todo)]);
+        app.move_card(false).unwrap();
+        assert_eq!(cards(&app, 0), ["a"]);
+        app.move_card(true).unwrap();
+        assert_eq!(cards(&app, 1), ["a", "b"]);
+        assert_eq!((app.board.column, app.board.row), (1, 0));
+        app.next_card(true);
+        for _ in 0..BOARD_STATES.len() {
+            app.move_card(true).unwrap();
+        }
+        assert_eq!(cards(&app, BOARD_STATES.len() - 1), ["b"]);
+        assert_eq!(selected_title(&app), "b");
+    }
+
+    #[test]
+    fn hidden_cards_leave_the_selection_in_their_column() {
+        let mut app = board(&[("a", State::Todo), ("b", State::InProgress)]);
+        app.filter.states = STATE_FILTERS[1].to_vec();
+        app.next_column(true);
+        // done notes are not shown, so the moved card can not stay selected
+        app.move_card(true).unwrap();
+        assert_eq!(cards(&app, 2), Vec::<String>::new());
+        assert_eq!((app.board.column, app.board.row), (2, 0));
+        assert_eq!(app.selected(), None);
+    }
+
+    #[test]
+    fn selection_is_clamped_when_a_move_is_undone() {
+        let mut app = board(&[("a", State::Todo), ("b", State::Todo)]);
+        app.move_card(true).unwrap();
+        app.next_column(false);
+        app.move_card(true).unwrap();
+        assert_eq!(cards(&app, 2), ["a", "b"]);
+        assert_eq!((app.board.column, app.board.row), (2, 1));
+
+        app.undo().unwrap();
+        assert_eq!(cards(&app, 2), ["a"]);
+        assert_eq!(selected_title(&app), "a");
+    }
+
+    #[test]
+    fn categories_are_renamed_and_merged() {
+        let mut app = app(&[("a", "wrok"), ("b", "work"), ("c", "home")]);
+        app.toggle_categories();
+        app.next_category_row(true);
+        app.next_category_row(true);
+        assert_eq!(app.selected_category().as_deref(), Some("wrok"));
+        app.show_input(NoteInputState::RenameCategory);
+        app.note_state.input = LineEditor::from("work");
+        app.submit_input().unwrap();
+        assert_eq!(
+            app.category_rows(),
+            [(String::from("home"), 1), (String::from("work"), 2)]
+        );
+        assert_eq!(app.selected_category().as_deref(), Some("work"));
+        assert!(!app.note_state.show_input_note);
+    }
+
+    #[test]
+    fn categories_can_not_be_renamed_to_nothing() {
+        let mut app = app(&[("a", "work")]);
+        app.toggle_categories();
+        app.show_input(NoteInputState::RenameCategory);
+        app.note_state.input = LineEditor::from("  ");
+        app.submit_input().unwrap();
+        assert!(app.popup.is_some());
+        assert!(app.note_state.show_input_note);
+        assert_eq!(app.notes.map[0].category, "work");
     }
 }
@@ -0,0 +1,159 @@
+use backend::note::Note;
+use chrono::Utc;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Error, ErrorKind, Result, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+/// Editor used when neither `$VISUAL` nor `$EDITOR` is set
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Edit the title and description of a note in the editor of the user
+/// Returns `None` when the title was emptied, which leaves the note as is
+pub fn edit(note: &Note) -> Result<Option<Note>> {
+    let (path, mut file) = create_temp_file()?;
+    let written = file.write_all(seed(note).as_bytes());
+    drop(file);
+    if let Err(e) = written {
+        fs::remove_file(&path)?;
+        return Err(e);
+    }
+
+    let status = launch(&path);
+    let text = fs::read_to_string(&path);
+    fs::remove_file(&path)?;
+
+    if !status?.success() {
+        return Err(Error::other("Editor exited with an error"));
+    }
+
+    Ok(parse(&text?).map(|(title, description)| Note {
+        title,
+        description,
+        updated_at: Utc::now().to_rfc3339(),
+        ..note.to_owned()
+    }))
+}
+
+/// Create a new file in the temporary directory that only the user can read
+/// The file must not exist yet, so a file or symlink that another user placed there is never
+/// written to
+fn create_temp_file() -> Result<(PathBuf, File)> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    for attempt in 0..100 {
+        let path = env::temp_dir().join(format!("vodo-{}-{}.md", process::id(), attempt));
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(Error::new(
+        ErrorKind::AlreadyExists,
+        "Unable to create a temporary file for the editor",
+    ))
+}
+
+/// Launch `$VISUAL` or `$EDITOR` on the file and wait for it to close
+fn launch(path: &Path) -> Result<process::ExitStatus> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| DEFAULT_EDITOR.to_owned());
+
+    // the editor might come with arguments, e.g. `code --wait`
+    let mut args = editor.split_whitespace();
+    let program = args.next().unwrap_or(DEFAULT_EDITOR);
+
+    Command::new(program)
+        .args(args)
+        .arg(path)
+        .status()
+        .map_err(|e| Error::new(e.kind(), format!("Unable to start {}: {}", program, e)))
+}
+
+/// Text the editor is opened with: the title, an empty line and the description
+fn seed(note: &Note) -> String {
+    format!(
+        "{}\n\n{}\n",
+        note.title,
+        note.description.as_deref().unwrap_or_default()
+    )
+}
+
+/// Split the edited text into the title and the description
+fn parse(text: &str) -> Option<(String, Option<String>)> {
+    let text = text.trim_start();
+    let (title, description) = text.split_once('\n').unwrap_or((text, ""));
+
+    let title = title.trim();
+    if title.is_empty() {
+        return None;
+    }
+
+    // keep the indentation of the first line, as it matters in markdown
+    let description = description.trim_start_matches(['\n', '\r']).trim_end();
+    let description = match description.is_empty() {
+        true => None,
+        false => Some(description.to_owned()),
+    };
+
+    Some((title.to_owned(), description))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_line_is_the_title() {
+        let parsed = parse("title\n\ndescription\n\n- item\n");
+        assert_eq!(
+            parsed,
+            Some((
+                String::from("title"),
+                Some(String::from("description\n\n- item"))
+            ))
+        );
+    }
+
+    #[test]
+    fn description_keeps_its_indentation() {
+        let parsed = parse("title\n\n    code\n");
+        assert_eq!(parsed.unwrap().1, Some(String::from("    code")));
+    }
+
+    #[test]
+    fn title_without_description() {
+        assert_eq!(parse("title"), Some((String::from("title"), None)));
+        assert_eq!(parse("title\n\n\n"), Some((String::from("title"), None)));
+    }
+
+    #[test]
+    fn empty_file_has_no_title() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("  \n\n"), None);
+    }
+
+    #[test]
+    fn leading_blank_lines_are_skipped() {
+        let parsed = parse("\n\n  title  \ndescription");
+        assert_eq!(
+            parsed,
+            Some((String::from("title"), Some(String::from("description"))))
+        );
+    }
+
+    #[test]
+    fn temp_files_are_new() {
+        let (first, _) = create_temp_file().unwrap();
+        let (second, _) = create_temp_file().unwrap();
+        assert_ne!(first, second);
+        fs::remove_file(first).unwrap();
+        fs::remove_file(second).unwrap();
+    }
+}
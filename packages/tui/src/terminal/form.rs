@@ -0,0 +1,285 @@
+use backend::note::{Note, State};
+use chrono::{DateTime, Local, Utc};
+use crossterm::event::KeyEvent;
+use uuid::Uuid;
+
+use super::app::{App, BOARD_STATES};
+use super::input::LineEditor;
+use super::keymap::Action;
+
+/// A field of the note form, in the order they are switched through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormField {
+    Title,
+    Category,
+    State,
+    Due,
+    Tags,
+    Save,
+    Cancel,
+}
+
+impl FormField {
+    /// All the fields, in the order they are switched through
+    pub const ALL: [FormField; 7] = [
+        FormField::Title,
+        FormField::Category,
+        FormField::State,
+        FormField::Due,
+        FormField::Tags,
+        FormField::Save,
+        FormField::Cancel,
+    ];
+
+    /// Label of the field as shown in the form
+    pub fn label(&self) -> &'static str {
+        match self {
+            FormField::Title => "Title",
+            FormField::Category => "Category",
+            FormField::State => "State",
+            FormField::Due => "Due",
+            FormField::Tags => "Tags",
+            FormField::Save => "Save",
+            FormField::Cancel => "Cancel",
+        }
+    }
+}
+
+/// Form to add a note or edit all the fields of one
+#[derive(Debug, Clone)]
+pub struct NoteForm {
+    /// Id of the note that is edited, none when a note is added
+    pub id: Option<Uuid>,
+
+    /// Title of the note, which can not be empty
+    pub title: LineEditor,
+
+    /// Category of the note, empty for none
+    pub category: LineEditor,
+
+    /// State of the note, switched through instead of typed
+    pub state: State,
+
+    /// Due date as YYYY-MM-DD, empty for none
+    pub due: LineEditor,
+
+    /// Tags separated by spaces or commas
+    pub tags: LineEditor,
+
+    /// Categories that are used, which the category is completed to
+    pub categories: Vec<String>,
+
+    /// Field that has the focus
+    pub focus: FormField,
+
+    /// Why the note could not be saved
+    pub error: Option<String>,
+}
+
+impl Default for NoteForm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NoteForm {
+    /// Form to add a note
+    pub fn new() -> Self {
+        Self {
+            id: None,
+            title: LineEditor::default(),
+            category: LineEditor::default(),
+            state: State::Todo,
+            due: LineEditor::default(),
+            tags: LineEditor::default(),
+            categories: Vec::new(),
+            focus: FormField::Title,
+            error: None,
+        }
+    }
+
+    /// Form to edit the note
+    pub fn edit(note: &Note) -> Self {
+        let due = note
+            .due_date()
+            .map(|d| DateTime::<Local>::from(d).format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        Self {
+            id: Some(note.id),
+            title: LineEditor::from(note.title.as_str()),
+            category: LineEditor::from(note.category.as_str()),
+            state: note.state.to_owned(),
+            due: LineEditor::from(due.as_str()),
+            tags: LineEditor::from(note.tags.join(" ").as_str()),
+            ..Self::new()
+        }
+    }
+
+    /// Edit the form with a key press, with the action the key is bound to while typing
+    /// The field actions switch fields and the choice actions switch the state or the button
+    /// In the category the next field action first completes the category, when it can
+    pub fn handle(&mut self, key: KeyEvent, action: Option<Action>) {
+        match (action, self.focus) {
+            (Some(Action::NextField), FormField::Category)
+                if self.category.complete(&self.categories) => {}
+            (Some(Action::NextField), _) => self.next_field(true),
+            (Some(Action::PreviousField), _) => self.next_field(false),
+            (Some(Action::NextChoice), FormField::State) => self.next_state(true),
+            (Some(Action::PreviousChoice), FormField::State) => self.next_state(false),
+            (Some(Action::PreviousChoice), FormField::Cancel) => self.focus = FormField::Save,
+            (Some(Action::NextChoice), FormField::Save) => self.focus = FormField::Cancel,
+            _ => {
+                if let Some(editor) = self.editor() {
+                    editor.handle(key);
+                }
+            }
+        }
+    }
+
+    /// Line editor of the field that has the focus, if it is a text field
+    pub fn editor(&mut self) -> Option<&mut LineEditor> {
+        match self.focus {
+            FormField::Title => Some(&mut self.title),
+            FormField::Category => Some(&mut self.category),
+            FormField::Due => Some(&mut self.due),
+            FormField::Tags => Some(&mut self.tags),
+            FormField::State | FormField::Save | FormField::Cancel => None,
+        }
+    }
+
+    /// Focus the field after the current one, or the one before it
+    pub fn next_field(&mut self, forward: bool) {
+        let len = FormField::ALL.len();
+        let i = FormField::ALL
+            .iter()
+            .position(|f| *f == self.focus)
+            .unwrap_or(0);
+        let i = match forward {
+            true => (i + 1) % len,
+            false => (i + len - 1) % len,
+        };
+        self.focus = FormField::ALL[i];
+    }
+
+    /// Switch to the state after the current one, or the one before it
+    pub fn next_state(&mut self, forward: bool) {
+        let len = BOARD_STATES.len();
+        let i = BOARD_STATES
+            .iter()
+            .position(|s| *s == self.state)
+            .unwrap_or(0);
+        let i = match forward {
+            true => (i + 1) % len,
+            false => (i + len - 1) % len,
+        };
+        self.state = BOARD_STATES[i].to_owned();
+    }
+
+    /// The note with the fields of the form, or why they are invalid
+    /// The invalid field gets the focus
+    pub fn apply(&mut self, note: Note) -> Result<Note, String> {
+        let title = self.title.value().trim();
+        if title.is_empty() {
+            self.focus = FormField::Title;
+            return Err(String::from("The title can not be empty"));
+        }
+        let ends_at = match Note::parse_ends_at(self.due.value()) {
+            Ok(ends_at) => ends_at,
+            Err(e) => {
+                self.focus = FormField::Due;
+                return Err(e.to_string());
+            }
+        };
+        Ok(Note {
+            title: title.to_owned(),
+            category: self.category.value().trim().to_owned(),
+            state: self.state.to_owned(),
+            ends_at,
+            tags: App::parse_tags(self.tags.value()),
+            updated_at: Utc::now().to_rfc3339(),
+            ..note
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::keymap::Keymap;
+    use crossterm::event::KeyCode;
+    use std::collections::HashMap;
+
+    /// Press a key in the form, with the default keys
+    fn press(form: &mut NoteForm, code: KeyCode) {
+        let key = KeyEvent::from(code);
+        let action = Keymap::new(&HashMap::new()).unwrap().input_action(key);
+        form.handle(key, action);
+    }
+
+    #[test]
+    fn fields_wrap_around() {
+        let mut form = NoteForm::new();
+        form.next_field(false);
+        assert_eq!(form.focus, FormField::Cancel);
+        press(&mut form, KeyCode::Tab);
+        press(&mut form, KeyCode::Tab);
+        assert_eq!(form.focus, FormField::Category);
+    }
+
+    #[test]
+    fn state_is_switched_with_left_and_right() {
+        let mut form = NoteForm::new();
+        form.focus = FormField::State;
+        press(&mut form, KeyCode::Right);
+        assert_eq!(form.state, State::InProgress);
+        press(&mut form, KeyCode::Left);
+        press(&mut form, KeyCode::Left);
+        press(&mut form, KeyCode::Left);
+        assert_eq!(form.state, State::Expired);
+    }
+
+    #[test]
+    fn edit_and_apply_keep_the_other_fields() {
+        let mut note = Note::new("title", String::from("work"), State::Done);
+        note.tags = vec![String::from("a"), String::from("b")];
+        note.description = Some(String::from("description"));
+        let mut form = NoteForm::edit(&note);
+        assert_eq!(form.tags.value(), "a b");
+        form.title.insert('!');
+        let edited = form.apply(note.to_owned()).unwrap();
+        assert_eq!(edited.title, "title!");
+        assert_eq!(edited.category, "work");
+        assert_eq!(edited.state, State::Done);
+        assert_eq!(edited.tags, note.tags);
+        assert_eq!(edited.description, note.description);
+    }
+
+    #[test]
+    fn tab_completes_the_category() {
+        let mut form = NoteForm::new();
+        form.categories = vec![String::from("work")];
+        form.focus = FormField::Category;
+        form.category = LineEditor::from("w");
+        press(&mut form, KeyCode::Tab);
+        assert_eq!(form.category.value(), "work");
+        assert_eq!(form.focus, FormField::Category);
+        press(&mut form, KeyCode::Tab);
+        assert_eq!(form.focus, FormField::State);
+    }
+
+    #[test]
+    fn invalid_fields_get_the_focus() {
+        let mut form = NoteForm::new();
+        form.focus = FormField::Save;
+        assert!(form
+            .apply(Note::new("", String::new(), State::Todo))
+            .is_err());
+        assert_eq!(form.focus, FormField::Title);
+        form.title = LineEditor::from("title");
+        form.due = LineEditor::from("tomorrow");
+        assert!(form
+            .apply(Note::new("", String::new(), State::Todo))
+            .is_err());
+        assert_eq!(form.focus, FormField::Due);
+    }
+}
@@ -1,9 +1,20 @@
 use crate::terminal::app::NoteInputState;
 
-use super::app::App;
-use backend::note::Notes;
+use super::app::{App, Popup, PopupKind, ViewRow, BOARD_STATES, STATE_FILTERS};
+use super::editor;
+use super::form::FormField;
+use super::keymap::{Action, Keymap};
+use super::markdown;
+use super::theme::Theme;
+use backend::config::Config;
+use backend::note::{Notes, State, TagMatch};
+use backend::query::SortKey;
+use backend::search::fuzzy_match;
+use chrono::{DateTime, Local};
 use crossterm::{
-    event::{read, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{
+        read, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -11,34 +22,135 @@ use std::io::{stdout, Error, Result, Stdout};
 use std::time::{Duration, Instant};
 use tui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Layout},
-    style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Cell, Paragraph, Row, Table},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Span, Spans},
+    widgets::{
+        Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, Wrap,
+    },
     Frame, Terminal,
 };
 
+/// Actions shown in the commands bar of the notes table
+const TABLE_HELP: &[(Action, &str)] = &[
+    (Action::Quit, "quit"),
+    (Action::Next, "down"),
+    (Action::Previous, "up"),
+    (Action::SwapDown, "move down"),
+    (Action::SwapUp, "move up"),
+    (Action::Delete, "delete"),
+    (Action::New, "new note"),
+    (Action::CycleState, "state"),
+    (Action::Edit, "edit note"),
+    (Action::EditInEditor, "edit in editor"),
+    (Action::Prioritize, "prioritize"),
+    (Action::Deprioritize, "deprioritize"),
+    (Action::Search, "search"),
+    (Action::FilterTags, "filter tags"),
+    (Action::ToggleTagMatch, "any/all tags"),
+    (Action::FilterState, "filter state"),
+    (Action::FilterCategory, "filter category"),
+    (Action::Sort, "sort"),
+    (Action::SortDirection, "sort direction"),
+    (Action::Group, "group"),
+    (Action::Board, "board"),
+    (Action::Detail, "details"),
+    (Action::OpenNotebook, "notebook"),
+    (Action::NextNotebook, "next notebook"),
+    (Action::Indent, "indent"),
+    (Action::Outdent, "outdent"),
+    (Action::Left, "fold"),
+    (Action::Right, "unfold"),
+    (Action::Checklist, "checklist"),
+    (Action::Categories, "categories"),
+    (Action::Archive, "archive"),
+    (Action::ShowArchive, "show archive"),
+    (Action::Undo, "undo"),
+    (Action::Redo, "redo"),
+];
+
+/// Actions shown in the commands bar of the checklist
+const CHECKLIST_HELP: &[(Action, &str)] = &[
+    (Action::Checklist, "close"),
+    (Action::Next, "down"),
+    (Action::Previous, "up"),
+    (Action::SwapDown, "move down"),
+    (Action::SwapUp, "move up"),
+    (Action::ToggleItem, "check"),
+    (Action::New, "new item"),
+    (Action::Delete, "delete"),
+    (Action::Undo, "undo"),
+    (Action::Redo, "redo"),
+];
+
+/// Actions shown in the commands bar of the board
+const BOARD_HELP: &[(Action, &str)] = &[
+    (Action::Board, "close"),
+    (Action::Left, "left"),
+    (Action::Right, "right"),
+    (Action::Next, "down"),
+    (Action::Previous, "up"),
+    (Action::MoveLeft, "move left"),
+    (Action::MoveRight, "move right"),
+    (Action::Detail, "details"),
+    (Action::EditInEditor, "edit note"),
+    (Action::Checklist, "checklist"),
+    (Action::Undo, "undo"),
+    (Action::Redo, "redo"),
+];
+
+/// Actions shown in the commands bar of the note form
+const FORM_HELP: &[(Action, &str)] = &[
+    (Action::NextField, "next field"),
+    (Action::PreviousField, "previous field"),
+    (Action::PreviousChoice, "previous state"),
+    (Action::NextChoice, "next state"),
+    (Action::Submit, "save"),
+    (Action::Cancel, "cancel"),
+];
+
+/// Actions shown in the commands bar of the category manager
+const CATEGORIES_HELP: &[(Action, &str)] = &[
+    (Action::Categories, "close"),
+    (Action::Next, "down"),
+    (Action::Previous, "up"),
+    (Action::Edit, "rename/merge"),
+    (Action::Detail, "show notes"),
+    (Action::Undo, "undo"),
+    (Action::Redo, "redo"),
+];
+
 /// Terminal tui for vodo
 pub struct VodoTerminal {
     terminal: Terminal<CrosstermBackend<Stdout>>,
     app: App,
     tick_rate: Duration,
+    /// Event that was read ahead to tell pasted text from typed keys, handled next
+    pending: Option<Event>,
 }
 
 impl VodoTerminal {
     /// Setup a general terminal
-    pub fn setup(notes: Notes) -> std::result::Result<Self, Error> {
+    pub fn setup(
+        notes: Notes,
+        config: Config,
+        keymap: Keymap,
+        theme: Theme,
+        popup: Option<Popup>,
+    ) -> std::result::Result<Self, Error> {
         // setup terminal
         enable_raw_mode()?;
         let mut stdout = stdout();
         execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
         let backend = CrosstermBackend::new(stdout);
 
-        let app = App::new(notes);
+        let app = App::new(notes, config, keymap, theme, popup);
 
         Ok(Self {
             terminal: Terminal::new(backend)?,
             app,
             tick_rate: Duration::from_millis(250),
+            pending: None,
         })
     }
 
@@ -55,8 +167,137 @@ impl VodoTerminal {
         self.terminal.show_cursor()
     }
 
+    /// Restore the terminal after it has been destructed
+    fn resume(&mut self) -> Result<()> {
+        enable_raw_mode()?;
+        execute!(
+            self.terminal.backend_mut(),
+            EnterAlternateScreen,
+            EnableMouseCapture
+        )?;
+        self.terminal.clear()
+    }
+
+    /// Open the selected note in the editor of the user and save the result
+    fn edit_in_editor(&mut self) -> Result<()> {
+        let idx = match self.app.selected() {
+            Some(idx) => idx,
+            None => return Ok(()),
+        };
+        let note = self.app.notes.map[idx].to_owned();
+
+        self.destruct()?;
+        let edited = editor::edit(&note);
+        self.resume()?;
+
+        // a failing editor leaves the note untouched
+        match edited {
+            Ok(Some(note)) => {
+                let saved = self.app.notes.update_by_id(note);
+                self.app.show_unsaved(saved);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                self.app.popup = Some(Popup {
+                    kind: PopupKind::Error,
+                    message: e.to_string(),
+                })
+            }
+        }
+        Ok(())
+    }
+
+    /// Do what the action does in the view that is shown, returns whether to quit
+    /// A change that can not be saved is kept, and shown in a popup
+    fn run_action(&mut self, action: Action) -> Result<bool> {
+        let mut saved = Ok(());
+        if self.app.show_categories {
+            match action {
+                Action::Quit | Action::Back | Action::Categories => self.app.toggle_categories(),
+                Action::Next => self.app.next_category_row(true),
+                Action::Previous => self.app.next_category_row(false),
+                Action::Edit => self.app.show_input(NoteInputState::RenameCategory),
+                Action::Detail => self.app.filter_selected_category(),
+                Action::Undo => saved = self.app.undo(),
+                Action::Redo => saved = self.app.redo(),
+                _ => {}
+            }
+        } else if self.app.show_checklist {
+            match action {
+                Action::Quit | Action::Back | Action::Checklist => self.app.toggle_checklist(),
+                Action::Next => self.app.next_item(),
+                Action::Previous => self.app.previous_item(),
+                Action::SwapDown => saved = self.app.swap_item_down(),
+                Action::SwapUp => saved = self.app.swap_item_up(),
+                Action::ToggleItem => saved = self.app.toggle_item(),
+                Action::New => self.app.show_input(NoteInputState::ChecklistItem),
+                Action::Delete => saved = self.app.delete_item(),
+                Action::Undo => saved = self.app.undo(),
+                Action::Redo => saved = self.app.redo(),
+                _ => {}
+            }
+        } else if self.app.show_board {
+            match action {
+                Action::Quit | Action::Back | Action::Board => self.app.toggle_board(),
+                Action::Left => self.app.next_column(false),
+                Action::Right => self.app.next_column(true),
+                Action::Next => self.app.next_card(true),
+                Action::Previous => self.app.next_card(false),
+                Action::MoveLeft => saved = self.app.move_card(false),
+                Action::MoveRight => saved = self.app.move_card(true),
+                Action::Detail => self.app.toggle_detail(),
+                Action::EditInEditor => self.edit_in_editor()?,
+                Action::Checklist => self.app.toggle_checklist(),
+                Action::Undo => saved = self.app.undo(),
+                Action::Redo => saved = self.app.redo(),
+                _ => {}
+            }
+        } else {
+            match action {
+                Action::Back if !self.app.search.is_empty() => self.app.clear_search(),
+                Action::Quit | Action::Back => return Ok(true),
+                Action::Search => self.app.show_input(NoteInputState::Search),
+                Action::Next => self.app.next(),
+                Action::Previous => self.app.previous(),
+                Action::SwapDown => saved = self.app.swap_down(),
+                Action::SwapUp => saved = self.app.swap_up(),
+                Action::Delete => saved = self.app.delete(),
+                Action::New => self.app.show_input(NoteInputState::New),
+                Action::CycleState => saved = self.app.update_state(),
+                Action::Edit => self.app.show_input(NoteInputState::Editting),
+                Action::EditInEditor => self.edit_in_editor()?,
+                Action::Prioritize => saved = self.app.prioritize(),
+                Action::Deprioritize => saved = self.app.deprioritize(),
+                Action::FilterTags => self.app.show_input(NoteInputState::TagFilter),
+                Action::ToggleTagMatch => self.app.toggle_tag_match(),
+                Action::Group => self.app.toggle_group_by_category(),
+                Action::Board => self.app.toggle_board(),
+                Action::Sort => self.app.next_sort(),
+                Action::SortDirection => self.app.toggle_sort_direction(),
+                Action::FilterState => self.app.next_state_filter(),
+                Action::FilterCategory => self.app.next_category_filter(),
+                Action::Detail => self.app.toggle_detail(),
+                Action::NextNotebook => self.app.next_notebook(),
+                Action::OpenNotebook => self.app.show_input(NoteInputState::Notebook),
+                Action::Left => self.app.collapse(),
+                Action::Right => self.app.expand(),
+                Action::Indent => saved = self.app.indent(),
+                Action::Outdent => saved = self.app.outdent(),
+                Action::Checklist => self.app.toggle_checklist(),
+                Action::Categories => self.app.toggle_categories(),
+                Action::Archive => saved = self.app.archive(),
+                Action::ShowArchive => self.app.toggle_archive(),
+                Action::Undo => saved = self.app.undo(),
+                Action::Redo => saved = self.app.redo(),
+                _ => {}
+            }
+        }
+        self.app.show_unsaved(saved);
+        Ok(false)
+    }
+
     pub fn run_app(&mut self) -> Result<()> {
-        let last_tick = Instant::now();
+        let mut last_tick = Instant::now();
         loop {
             self.terminal.draw(|f| VodoTerminal::ui(f, &mut self.app))?;
 
@@ -64,145 +305,636 @@ impl VodoTerminal {
                 .tick_rate
                 .checked_sub(last_tick.elapsed())
                 .unwrap_or_else(|| Duration::from_secs(0));
-            if crossterm::event::poll(timeout)? {
-                if let Event::Key(key) = read()? {
-                    if !self.app.note_state.show_input_note {
-                        match key.code {
-                            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
-                            KeyCode::Down => self.app.next(),
-                            KeyCode::Up => self.app.previous(),
-                            KeyCode::Char('j') => self.app.next(),
-                            KeyCode::Char('J') => self.app.swap_down(),
-                            KeyCode::Char('k') => self.app.previous(),
-                            KeyCode::Char('K') => self.app.swap_up(),
-                            KeyCode::Char('d') => self.app.delete(),
-                            KeyCode::Char('n') => self.app.show_input(NoteInputState::New),
-                            KeyCode::Char('s') => self.app.update_state(),
-                            KeyCode::Char('e') => self.app.show_input(NoteInputState::Editting),
-                            KeyCode::Char('p') => self.app.prioritize(),
-                            KeyCode::Char('P') => self.app.deprioritize(),
-                            _ => {}
+            let event = match self.pending.take() {
+                Some(event) => Some(event),
+                None if crossterm::event::poll(timeout)? => Some(read()?),
+                None => None,
+            };
+            if let Some(Event::Key(key)) = event {
+                if self.app.popup.is_some() {
+                    self.app.dismiss_popup();
+                } else if !self.app.note_state.show_input_note {
+                    let quit = match self.app.keymap.action(key) {
+                        Some(action) => self.run_action(action)?,
+                        None => false,
+                    };
+                    if quit {
+                        return Ok(());
+                    }
+                } else {
+                    // without bracketed paste, a line break that is directly followed by
+                    // more text is pasted text instead of the enter key
+                    let pasted = key.code == KeyCode::Enter && self.text_follows()?;
+                    let search = matches!(self.app.note_state.input_state, NoteInputState::Search);
+                    match (key.code, self.app.keymap.input_action(key)) {
+                        (KeyCode::Enter, _) if pasted => {
+                            self.app.edit_input(KeyEvent::from(KeyCode::Char(' ')))
+                        }
+                        (_, Some(Action::Submit)) => {
+                            let saved = self.app.submit_input();
+                            self.app.show_unsaved(saved);
                         }
-                    } else {
-                        match self.app.note_state.input_state {
-                            NoteInputState::New => {
-                                match key.code {
-                                    KeyCode::Char(c) => self.app.note_state.input.push(c),
-                                    KeyCode::Backspace => {
-                                        self.app.note_state.input.pop();
-                                    }
-                                    KeyCode::Enter => self.app.add_note(),
-                                    KeyCode::Esc => self.app.reset(),
-                                    _ => {}
-                                };
-                            }
-                            NoteInputState::Editting => {
-                                match key.code {
-                                    KeyCode::Char(c) => self.app.note_state.input.push(c),
-                                    KeyCode::Backspace => {
-                                        self.app.note_state.input.pop();
-                                    }
-                                    KeyCode::Enter => self.app.edit_note(),
-                                    KeyCode::Esc => self.app.reset(),
-                                    _ => {}
-                                };
-                            }
-                            NoteInputState::Category => {
-                                match key.code {
-                                    KeyCode::Char(c) => self.app.note_state.category.push(c),
-                                    KeyCode::Backspace => {
-                                        self.app.note_state.category.pop();
-                                    }
-                                    KeyCode::Enter => self.app.set_category(),
-                                    KeyCode::Esc => self.app.reset(),
-                                    _ => {}
-                                };
-                            }
-                            _ => panic!("Unknown state"),
-                        };
+                        (_, Some(Action::Cancel)) if search => self.app.clear_search(),
+                        (_, Some(Action::Cancel)) => self.app.reset(),
+                        (KeyCode::Down, _) if search => self.app.next(),
+                        (KeyCode::Up, _) if search => self.app.previous(),
+                        _ => self.app.edit_input(key),
                     }
                 }
             }
+            if last_tick.elapsed() >= self.tick_rate {
+                let saved = self.app.on_tick();
+                self.app.show_unsaved(saved);
+                last_tick = Instant::now();
+            }
         }
     }
 
+    /// Whether the next event is already waiting and is text, which is read ahead to be handled
+    /// next
+    fn text_follows(&mut self) -> Result<bool> {
+        if !crossterm::event::poll(Duration::from_secs(0))? {
+            return Ok(false);
+        }
+        let next = read()?;
+        let text = is_text(&next);
+        self.pending = Some(next);
+        Ok(text)
+    }
+
+    /// Render the details and the markdown description of the selected note
+    fn detail<B: Backend>(f: &mut Frame<B>, app: &App, rect: Rect) {
+        let block = Block::default().borders(Borders::ALL).title("Details");
+        let note = match app.selected().and_then(|idx| app.notes.get(idx)) {
+            Some(note) => note,
+            None => {
+                f.render_widget(Paragraph::new("No note selected").block(block), rect);
+                return;
+            }
+        };
+
+        let label = app.theme.header;
+        let mut lines = vec![
+            Spans::from(Span::styled(
+                note.title.to_owned(),
+                Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            )),
+            Spans::from(vec![
+                Span::styled("State: ", label),
+                Span::styled(
+                    String::from(note.state.to_owned()),
+                    app.theme.state(&note.state),
+                ),
+            ]),
+            Spans::from(vec![
+                Span::styled("Category: ", label),
+                Span::styled(note.category.to_owned(), app.theme.category(&note.category)),
+            ]),
+            Spans::from(vec![
+                Span::styled("Tags: ", label),
+                Span::raw(note.tags.join(" ")),
+            ]),
+            Spans::from(""),
+        ];
+        match &note.description {
+            Some(description) => lines.extend(markdown::render(description, app.theme.code)),
+            None => lines.push(Spans::from(Span::styled("No description", app.theme.dim))),
+        }
+
+        let p = Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false });
+        f.render_widget(p, rect);
+    }
+
+    /// Render the checklist of the selected note
+    fn checklist<B: Backend>(f: &mut Frame<B>, app: &mut App, rect: Rect) {
+        let note = match app.selected().and_then(|idx| app.notes.get(idx)) {
+            Some(note) => note,
+            None => return,
+        };
+        let title = match note.progress() {
+            Some((done, total)) => format!("Checklist - {} ({}/{})", note.title, done, total),
+            None => format!("Checklist - {}", note.title),
+        };
+        let block = Block::default().borders(Borders::ALL).title(title);
+        if note.checklist.is_empty() {
+            let text = match app.keymap.key(Action::New) {
+                Some(key) => format!("No items, press {} to add one", key),
+                None => String::from("No items"),
+            };
+            let text = Span::styled(text, app.theme.dim);
+            f.render_widget(Paragraph::new(text).block(block), rect);
+            return;
+        }
+        let items: Vec<ListItem> = note
+            .checklist
+            .iter()
+            .map(|item| {
+                let (check, style) = match item.done {
+                    true => ("[x] ", Style::default().add_modifier(Modifier::CROSSED_OUT)),
+                    false => ("[ ] ", Style::default()),
+                };
+                ListItem::new(Spans::from(vec![
+                    Span::raw(check),
+                    Span::styled(item.title.to_owned(), style),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(app.theme.selected);
+        f.render_stateful_widget(list, rect, &mut app.checklist);
+    }
+
+    /// Render the notes as cards in a column per state
+    fn board<B: Backend>(f: &mut Frame<B>, app: &App, rect: Rect, title: String) {
+        let block = Block::default().borders(Borders::ALL).title(title);
+        let inner = block.inner(rect);
+        f.render_widget(block, rect);
+
+        let constraints = vec![Constraint::Ratio(1, BOARD_STATES.len() as u32); BOARD_STATES.len()];
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(constraints)
+            .split(inner);
+        for (column, state) in BOARD_STATES.iter().enumerate() {
+            let cards: Vec<ListItem> = app
+                .board_cards(column)
+                .into_iter()
+                .map(|idx| {
+                    let note = &app.notes.map[idx];
+                    let mut info = Vec::new();
+                    if let Some(due) = note.due_date() {
+                        info.push(DateTime::<Local>::from(due).format("%Y-%m-%d").to_string());
+                    }
+                    if let Some((done, total)) = note.progress() {
+                        info.push(format!("{}/{}", done, total));
+                    }
+                    let mut details = Vec::new();
+                    if !note.category.is_empty() {
+                        let style = app.theme.category(&note.category);
+                        details.push(Span::styled(format!("{} ", note.category), style));
+                    }
+                    details.push(Span::styled(info.join(" "), app.theme.dim));
+                    ListItem::new(vec![
+                        VodoTerminal::highlight(&note.title, app.search.value(), app.theme.matched),
+                        Spans::from(details),
+                    ])
+                })
+                .collect();
+            let name = match state {
+                State::None => String::from("No state"),
+                state => String::from(state.to_owned()),
+            };
+            let current = column == app.board.column;
+            let border = match current {
+                true => Style::default().add_modifier(Modifier::BOLD),
+                false => app.theme.dim,
+            };
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .border_style(border)
+                .title(Span::styled(
+                    format!("{} ({})", name, cards.len()),
+                    app.theme.state(state),
+                ));
+            let list = List::new(cards)
+                .block(block)
+                .highlight_style(app.theme.selected);
+            let mut state = ListState::default();
+            if current {
+                state.select(Some(app.board.row));
+            }
+            f.render_stateful_widget(list, columns[column], &mut state);
+        }
+    }
+
+    /// Render the category manager, with the number of notes in every category
+    fn categories<B: Backend>(f: &mut Frame<B>, app: &mut App, rect: Rect, title: String) {
+        let block = Block::default().borders(Borders::ALL).title(title);
+        let rows = app.category_rows();
+        if rows.is_empty() {
+            let text = Span::styled("No categories", app.theme.dim);
+            f.render_widget(Paragraph::new(text).block(block), rect);
+            return;
+        }
+        let items: Vec<ListItem> = rows
+            .into_iter()
+            .map(|(category, count)| {
+                let style = app.theme.category(&category);
+                ListItem::new(Spans::from(vec![
+                    Span::styled(category, style),
+                    Span::styled(format!(" ({})", count), app.theme.dim),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(app.theme.selected);
+        f.render_stateful_widget(list, rect, &mut app.category_list);
+    }
+
+    /// Text with the characters that match the search highlighted
+    fn highlight(text: &str, search: &str, style: Style) -> Spans<'static> {
+        let positions = match fuzzy_match(search, text) {
+            Some(m) => m.positions,
+            None => return Spans::from(text.to_owned()),
+        };
+        let spans: Vec<Span> = text
+            .chars()
+            .enumerate()
+            .map(|(i, c)| match positions.contains(&i) {
+                true => Span::styled(c.to_string(), style),
+                false => Span::raw(c.to_string()),
+            })
+            .collect();
+        Spans::from(spans)
+    }
+
     fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         let rects = Layout::default()
             .constraints([Constraint::Min(10), Constraint::Length(3)].as_ref())
             .split(f.size());
 
         let selected_style = match app.note_state.should_delete {
-            true => Style::default()
-                .add_modifier(Modifier::REVERSED)
-                .fg(Color::Red),
-            false => Style::default().add_modifier(Modifier::REVERSED),
+            true => app.theme.delete,
+            false => app.theme.selected,
         };
 
         // --- table ---
-        let header_cells = ["State", "Category", "Note"]
+        let arrow = match app.sort.descending {
+            true => "▼",
+            false => "▲",
+        };
+        let sorted = match app.sort.key {
+            SortKey::Manual => None,
+            SortKey::State => Some("State"),
+            SortKey::Category => Some("Category"),
+            SortKey::Due => Some("Due"),
+            SortKey::Created | SortKey::Updated => Some("Note"),
+        };
+        let header_cells = ["State", "Category", "Due", "Progress", "Tags", "Note"]
             .iter()
-            .map(|h| Cell::from(*h).style(Style::default().add_modifier(Modifier::BOLD)));
+            .map(|h| match Some(*h) == sorted {
+                true if *h == "Note" => format!("{} ({} {})", h, app.sort.key.name(), arrow),
+                true => format!("{} {}", h, arrow),
+                false => h.to_string(),
+            })
+            .map(|h| Cell::from(h).style(app.theme.header));
         let header = Row::new(header_cells).height(1);
-        let rows = app.notes.map.iter().map(|item| {
+        let view_rows = app.rows();
+        let rows = view_rows.iter().map(|row| {
+            let row = match row {
+                ViewRow::Note(row) => row,
+                ViewRow::Group {
+                    category,
+                    count,
+                    collapsed,
+                } => {
+                    let marker = match collapsed {
+                        true => "▸",
+                        false => "▾",
+                    };
+                    let name = match category.is_empty() {
+                        true => "(none)",
+                        false => category.as_str(),
+                    };
+                    let bold = app.theme.header;
+                    return Row::new(vec![
+                        Cell::from(format!("{} ({})", marker, count)).style(bold),
+                        Cell::from(name.to_owned()).style(bold.patch(app.theme.category(category))),
+                    ]);
+                }
+            };
+            let item = &app.notes.map[row.idx];
+            let marker = match row.has_children {
+                true if app.collapsed.contains(&item.id) => "▸ ",
+                true => "▾ ",
+                false => "  ",
+            };
             let cells = vec![
-                Cell::from(String::from(item.state.to_owned())),
-                Cell::from(item.category.to_owned()),
-                Cell::from(item.title.to_owned()),
+                Cell::from(String::from(item.state.to_owned())).style(app.theme.state(&item.state)),
+                Cell::from(VodoTerminal::highlight(
+                    &item.category,
+                    app.search.value(),
+                    app.theme.matched,
+                ))
+                .style(app.theme.category(&item.category)),
+                Cell::from(
+                    item.due_date()
+                        .map(|d| DateTime::<Local>::from(d).format("%Y-%m-%d").to_string())
+                        .unwrap_or_default(),
+                ),
+                Cell::from(
+                    item.progress()
+                        .map(|(done, total)| format!("{}/{}", done, total))
+                        .unwrap_or_default(),
+                ),
+                Cell::from(VodoTerminal::highlight(
+                    &item.tags.join(" "),
+                    app.search.value(),
+                    app.theme.matched,
+                )),
+                Cell::from({
+                    let mut title =
+                        VodoTerminal::highlight(&item.title, app.search.value(), app.theme.matched);
+                    let indent = format!("{}{}", "  ".repeat(row.depth), marker);
+                    title.0.insert(0, Span::raw(indent));
+                    title
+                }),
             ];
             Row::new(cells)
         });
 
+        let view = match (app.show_categories, app.show_board, app.show_archive) {
+            (true, _, _) => "Categories",
+            (false, true, _) => "Board",
+            (false, false, true) => "Archive",
+            (false, false, false) => "Notes",
+        };
+        let mut title = format!("{} - {}", view, app.notes.notebook());
+        if !app.filter.states.is_empty() {
+            let states = match app.filter.states.as_slice() == STATE_FILTERS[1] {
+                true => String::from("not done"),
+                false => app
+                    .filter
+                    .states
+                    .iter()
+                    .map(|s| String::from(s.to_owned()).to_lowercase())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            };
+            title.push_str(&format!(" [state: {}]", states));
+        }
+        if let Some(category) = &app.filter.category {
+            title.push_str(&format!(" [category: {}]", category));
+        }
+        if !app.filter.tags.is_empty() {
+            title.push_str(&format!(
+                " [tags ({}): {}]",
+                match app.filter.tag_match {
+                    TagMatch::Any => "any",
+                    TagMatch::All => "all",
+                },
+                app.filter.tags.join(" ")
+            ));
+        }
+        if !app.search.is_empty() {
+            title.push_str(&format!(" [search: {}]", app.search.value()));
+        }
         let table = Table::new(rows)
-            .block(Block::default().borders(Borders::ALL).title("Notes"))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(title.to_owned()),
+            )
             .header(header)
             .highlight_style(selected_style)
             .widths(&[
                 Constraint::Percentage(10),
                 Constraint::Percentage(10),
+                Constraint::Length(10),
+                Constraint::Length(8),
+                Constraint::Percentage(15),
                 Constraint::Percentage(100),
             ]);
-        f.render_stateful_widget(table, rects[0], &mut app.state);
+        let table_rect = match app.show_detail || app.show_checklist {
+            true => {
+                let columns = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                    .split(rects[0]);
+                match app.show_checklist {
+                    true => VodoTerminal::checklist(f, app, columns[1]),
+                    false => VodoTerminal::detail(f, app, columns[1]),
+                }
+                columns[0]
+            }
+            false => rects[0],
+        };
+        match (app.show_categories, app.show_board) {
+            (true, _) => VodoTerminal::categories(f, app, table_rect, title),
+            (false, true) => VodoTerminal::board(f, app, table_rect, title),
+            (false, false) => f.render_stateful_widget(table, table_rect, &mut app.state),
+        }
         // -------------
 
         // --- commands ---
         if !app.note_state.show_input_note {
             let b = Block::default().borders(Borders::ALL).title("Commands");
-            let text = Paragraph::new(
-                "(q) quit | (j|J) down | (k|K) up | (d) delete | (n) new note | (e) edit note | (p|P) prioritize",
-            )
+            let text = Paragraph::new(app.keymap.help(
+                match (app.show_categories, app.show_checklist, app.show_board) {
+                    (true, _, _) => CATEGORIES_HELP,
+                    (false, true, _) => CHECKLIST_HELP,
+                    (false, false, true) => BOARD_HELP,
+                    (false, false, false) => TABLE_HELP,
+                },
+            ))
             .block(b);
             f.render_widget(text, rects[1]);
         }
         // ----------------
 
         // --- new note ---
-        // TODO: one giant match
-        if app.note_state.show_input_note {
-            let (title, text, len) = match app.note_state.input_state {
-                NoteInputState::Editting => (
-                    "Edit Note",
-                    app.note_state.input.as_ref(),
-                    app.note_state.input.len(),
-                ),
-                NoteInputState::New => (
-                    "New Note",
-                    app.note_state.input.as_ref(),
-                    app.note_state.input.len(),
-                ),
-                NoteInputState::Category => (
-                    "Category",
-                    app.note_state.category.as_ref(),
-                    app.note_state.category.len(),
-                ),
+        let form = matches!(
+            app.note_state.input_state,
+            NoteInputState::New | NoteInputState::Editting
+        );
+        if form {
+            VodoTerminal::form(f, app, rects[1]);
+        } else if app.note_state.show_input_note {
+            let title = match app.note_state.input_state {
+                NoteInputState::TagFilter => "Filter on tags (empty for all notes)",
+                NoteInputState::Notebook => "Open or create notebook",
+                NoteInputState::ChecklistItem => "New checklist item",
+                NoteInputState::Search => "Search (enter to keep, esc to clear)",
+                NoteInputState::RenameCategory => {
+                    "Rename category (tab completes, an existing category is merged into)"
+                }
                 _ => panic!("Unknown state"),
             };
             let block = Block::default().title(title).borders(Borders::ALL);
-            let p = Paragraph::new(text)
-                .style(Style::default().fg(Color::White))
-                .block(block);
-            f.set_cursor(rects[1].x + len as u16 + 1, rects[1].y + 1);
+            let width = block.inner(rects[1]).width;
+            let suffix = match app.note_state.input_state {
+                NoteInputState::RenameCategory => {
+                    let categories = app.notes.categories();
+                    app.note_state.input.completion_suffix(&categories)
+                }
+                _ => String::new(),
+            };
+            let (text, cursor) = match app.editor() {
+                Some(editor) => editor.view(width as usize),
+                None => (String::new(), 0),
+            };
+            let text = Spans::from(vec![
+                Span::styled(text, app.theme.input),
+                Span::styled(suffix, app.theme.dim),
+            ]);
+            let p = Paragraph::new(text).block(block);
+            f.set_cursor(rects[1].x + cursor as u16 + 1, rects[1].y + 1);
             f.render_widget(p, rects[1]);
         }
         // ----------------
+
+        // --- popup ---
+        if let Some(popup) = &app.popup {
+            VodoTerminal::popup(f, popup, &app.theme);
+        }
+        // ----------------
+    }
+
+    /// Render the form to add or edit a note in the middle of the screen, with its keys in the
+    /// rect of the commands
+    fn form<B: Backend>(f: &mut Frame<B>, app: &mut App, commands: Rect) {
+        let help = app.keymap.help(FORM_HELP);
+        let block = Block::default().borders(Borders::ALL).title("Commands");
+        f.render_widget(Paragraph::new(help).block(block), commands);
+
+        let title = match app.note_state.input_state {
+            NoteInputState::New => "New Note",
+            _ => "Edit Note",
+        };
+        let area = f.size();
+        let width = area.width.min(60);
+        let height = area.height.min(10);
+        let rect = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+        let block = Block::default().title(title).borders(Borders::ALL);
+        let inner = block.inner(rect);
+        f.render_widget(Clear, rect);
+        f.render_widget(block, rect);
+
+        // labels are padded to the same width, with the values after them
+        let label_width = 10;
+        let value_width = inner.width.saturating_sub(label_width) as usize;
+        let theme = &app.theme;
+        let form = &mut app.note_state.form;
+        let focus = form.focus;
+        let mut lines = Vec::new();
+        let mut cursor = None;
+        for (row, field) in FormField::ALL.iter().enumerate() {
+            let label = match *field == focus {
+                true => Span::styled(format!("{:<9}", field.label()), theme.selected),
+                false => Span::styled(format!("{:<9}", field.label()), theme.header),
+            };
+            let editor = match field {
+                FormField::Title => &mut form.title,
+                FormField::Category => &mut form.category,
+                FormField::Due => &mut form.due,
+                FormField::Tags => &mut form.tags,
+                FormField::State => {
+                    let name = match form.state {
+                        State::None => String::from("No state"),
+                        ref state => String::from(state.to_owned()),
+                    };
+                    let value = Span::styled(format!("◂ {} ▸", name), theme.state(&form.state));
+                    lines.push(Spans::from(vec![label, Span::raw(" "), value]));
+                    continue;
+                }
+                // the buttons are shown together below the fields
+                FormField::Save | FormField::Cancel => continue,
+            };
+            let suffix = match field {
+                FormField::Category => editor.completion_suffix(&form.categories),
+                _ => String::new(),
+            };
+            let (text, column) = editor.view(value_width);
+            if *field == focus {
+                cursor = Some((column as u16, row as u16));
+            }
+            lines.push(Spans::from(vec![
+                label,
+                Span::raw(" "),
+                Span::styled(text, theme.input),
+                Span::styled(suffix, theme.dim),
+            ]));
+        }
+        let button = |field: FormField| match field == focus {
+            true => Span::styled(format!("[ {} ]", field.label()), theme.selected),
+            false => Span::raw(format!("[ {} ]", field.label())),
+        };
+        lines.push(Spans::from(""));
+        lines.push(Spans::from(vec![
+            Span::raw(" ".repeat(label_width as usize)),
+            button(FormField::Save),
+            Span::raw("  "),
+            button(FormField::Cancel),
+        ]));
+        if let Some(error) = &form.error {
+            lines.push(Spans::from(Span::styled(error.to_owned(), theme.error)));
+        }
+        f.render_widget(Paragraph::new(lines), inner);
+        if let Some((column, row)) = cursor {
+            f.set_cursor(inner.x + label_width + column, inner.y + row);
+        }
+    }
+
+    /// Render a popup in the middle of the screen
+    fn popup<B: Backend>(f: &mut Frame<B>, popup: &Popup, theme: &Theme) {
+        let (title, style) = match popup.kind {
+            PopupKind::Warning => ("Warning", theme.warning),
+            PopupKind::Error => ("Error", theme.error),
+        };
+        let area = f.size();
+        let width = area.width.min(60);
+        let height = area.height.min(8);
+        let rect = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+        let block = Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(style);
+        let text = vec![
+            Spans::from(popup.message.as_str()),
+            Spans::from(""),
+            Spans::from(Span::styled("Press any key to continue", theme.dim)),
+        ];
+        let p = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
+        f.render_widget(Clear, rect);
+        f.render_widget(p, rect);
+    }
+}
+
+/// Whether the event is a key that types a character, as opposed to a command like enter, a
+/// key with ctrl or alt, or a resize
+fn is_text(event: &Event) -> bool {
+    match event {
+        Event::Key(key) => {
+            matches!(key.code, KeyCode::Char(_))
+                && !key
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_characters_are_pasted_text() {
+        assert!(is_text(&Event::Key(KeyEvent::from(KeyCode::Char('a')))));
+        assert!(is_text(&Event::Key(KeyEvent::new(
+            KeyCode::Char('A'),
+            KeyModifiers::SHIFT
+        ))));
+        assert!(!is_text(&Event::Key(KeyEvent::from(KeyCode::Enter))));
+        assert!(!is_text(&Event::Key(KeyEvent::from(KeyCode::Down))));
+        assert!(!is_text(&Event::Key(KeyEvent::new(
+            KeyCode::Char('r'),
+            KeyModifiers::CONTROL
+        ))));
+        assert!(!is_text(&Event::Resize(80, 20)));
     }
 }
@@ -0,0 +1,391 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// A single line of text that is typed in, with a cursor that can be moved around
+/// The cursor moves over graphemes and the text scrolls horizontally when it does not fit
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LineEditor {
+    /// The text that is typed in
+    text: String,
+
+    /// Byte index of the cursor in the text, always at the start of a grapheme
+    cursor: usize,
+
+    /// Column of the text that is shown first, when the text does not fit
+    scroll: usize,
+}
+
+impl From<&str> for LineEditor {
+    fn from(text: &str) -> Self {
+        let mut editor = LineEditor::default();
+        editor.insert_str(text);
+        editor
+    }
+}
+
+impl LineEditor {
+    /// The text that is typed in
+    pub fn value(&self) -> &str {
+        &self.text
+    }
+
+    /// Whether no text is typed in
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Remove all the text
+    pub fn clear(&mut self) {
+        *self = LineEditor::default();
+    }
+
+    /// Edit the text with a key press, returns whether the key edits text or moves the cursor
+    ///
+    /// Besides typing there is left/right, home/end (ctrl-a/ctrl-e), ctrl-left/right
+    /// (alt-b/alt-f) to jump words, backspace, delete, ctrl-w (alt-backspace) to delete the
+    /// word before the cursor, ctrl-u to delete to the start and ctrl-k to delete to the end
+    pub fn handle(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Char('a') if ctrl => self.home(),
+            KeyCode::Char('e') if ctrl => self.end(),
+            KeyCode::Char('w') if ctrl => self.delete_word_before(),
+            KeyCode::Char('u') if ctrl => self.delete_to_start(),
+            KeyCode::Char('k') if ctrl => self.delete_to_end(),
+            KeyCode::Char('b') if alt => self.word_left(),
+            KeyCode::Char('f') if alt => self.word_right(),
+            KeyCode::Char(c) if !ctrl && !alt => self.insert(c),
+            KeyCode::Left if ctrl => self.word_left(),
+            KeyCode::Right if ctrl => self.word_right(),
+            KeyCode::Left => self.left(),
+            KeyCode::Right => self.right(),
+            KeyCode::Home => self.home(),
+            KeyCode::End => self.end(),
+            KeyCode::Backspace if ctrl || alt => self.delete_word_before(),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Type a character at the cursor
+    pub fn insert(&mut self, c: char) {
+        self.insert_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Type text at the cursor, like pasted text, where line breaks and tabs become spaces
+    /// and other control characters are left out
+    pub fn insert_str(&mut self, text: &str) {
+        let text: String = text
+            .chars()
+            .filter_map(|c| match c {
+                '\n' | '\t' => Some(' '),
+                '\r' => None,
+                c if c.is_control() => None,
+                c => Some(c),
+            })
+            .collect();
+        // text can join the graphemes around it, the cursor goes behind the grapheme it ends in
+        let after = self.text.len() - self.cursor;
+        self.text.insert_str(self.cursor, &text);
+        self.cursor = self.boundary(self.text.len() - after);
+    }
+
+    /// Delete the grapheme before the cursor
+    pub fn backspace(&mut self) {
+        let start = self.previous(self.cursor);
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    /// Delete the grapheme after the cursor
+    pub fn delete(&mut self) {
+        let end = self.next(self.cursor);
+        self.text.replace_range(self.cursor..end, "");
+    }
+
+    /// Delete the word before the cursor, including the whitespace after it
+    pub fn delete_word_before(&mut self) {
+        let start = self.word_start(self.cursor);
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    /// Delete the text before the cursor
+    pub fn delete_to_start(&mut self) {
+        self.text.replace_range(..self.cursor, "");
+        self.cursor = 0;
+    }
+
+    /// Delete the text after the cursor
+    pub fn delete_to_end(&mut self) {
+        self.text.truncate(self.cursor);
+    }
+
+    /// Move the cursor a grapheme to the left
+    pub fn left(&mut self) {
+        self.cursor = self.previous(self.cursor);
+    }
+
+    /// Move the cursor a grapheme to the right
+    pub fn right(&mut self) {
+        self.cursor = self.next(self.cursor);
+    }
+
+    /// Move the cursor to the start of the text
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    /// Move the cursor to the end of the text
+    pub fn end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /// Move the cursor to the start of the word before it
+    pub fn word_left(&mut self) {
+        self.cursor = self.word_start(self.cursor);
+    }
+
+    /// Move the cursor to the end of the word after it
+    pub fn word_right(&mut self) {
+        let mut i = self.cursor;
+        while i < self.text.len() && self.is_space_at(i) {
+            i = self.next(i);
+        }
+        while i < self.text.len() && !self.is_space_at(i) {
+            i = self.next(i);
+        }
+        self.cursor = i;
+    }
+
+    /// First of the candidates that starts with the text, ignoring case, and is not the text
+    pub fn completion<'a>(&self, candidates: &'a [String]) -> Option<&'a str> {
+        if self.text.is_empty() {
+            return None;
+        }
+        let typed = self.text.to_lowercase();
+        candidates
+            .iter()
+            .find(|c| **c != self.text && c.to_lowercase().starts_with(&typed))
+            .map(String::as_str)
+    }
+
+    /// Replace the text with the completion from the candidates, returns whether there was one
+    pub fn complete(&mut self, candidates: &[String]) -> bool {
+        match self.completion(candidates) {
+            Some(completion) => {
+                *self = LineEditor::from(completion);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The rest of the completion from the candidates, to show after the text
+    pub fn completion_suffix(&self, candidates: &[String]) -> String {
+        self.completion(candidates)
+            .map(|c| c.chars().skip(self.text.chars().count()).collect())
+            .unwrap_or_default()
+    }
+
+    /// The part of the text that fits in the width and the column of the cursor in it
+    /// The text scrolls just enough to keep the cursor in view
+    pub fn view(&mut self, width: usize) -> (String, usize) {
+        let width = width.max(1);
+        let column = self.text[..self.cursor].width();
+        if column < self.scroll {
+            self.scroll = column;
+        } else if column >= self.scroll + width {
+            self.scroll = column + 1 - width;
+        }
+
+        let mut visible = String::new();
+        let mut start = 0;
+        for grapheme in self.text.graphemes(true) {
+            let end = start + grapheme.width();
+            if start >= self.scroll && end <= self.scroll + width {
+                visible.push_str(grapheme);
+            } else if start < self.scroll && end > self.scroll {
+                // a wide grapheme that is cut off on the left is left out, keeping its place
+                visible.push_str(&" ".repeat(end - self.scroll));
+            }
+            start = end;
+        }
+        (visible, column - self.scroll)
+    }
+
+    /// Index of the grapheme before the index
+    fn previous(&self, i: usize) -> usize {
+        self.text[..i]
+            .grapheme_indices(true)
+            .next_back()
+            .map_or(0, |(start, _)| start)
+    }
+
+    /// Index of the grapheme after the index
+    fn next(&self, i: usize) -> usize {
+        self.text[i..]
+            .graphemes(true)
+            .next()
+            .map_or(i, |grapheme| i + grapheme.len())
+    }
+
+    /// The index, or the end of the grapheme it is in
+    fn boundary(&self, i: usize) -> usize {
+        if i == 0 {
+            return 0;
+        }
+        self.text
+            .grapheme_indices(true)
+            .map(|(start, grapheme)| start + grapheme.len())
+            .find(|end| *end >= i)
+            .unwrap_or(self.text.len())
+    }
+
+    /// Start of the word before the index, skipping the whitespace in between
+    fn word_start(&self, i: usize) -> usize {
+        let mut i = i;
+        while i > 0 && self.is_space_at(self.previous(i)) {
+            i = self.previous(i);
+        }
+        while i > 0 && !self.is_space_at(self.previous(i)) {
+            i = self.previous(i);
+        }
+        i
+    }
+
+    /// Whether the grapheme at the index is whitespace
+    fn is_space_at(&self, i: usize) -> bool {
+        self.text[i..]
+            .chars()
+            .next()
+            .is_some_and(char::is_whitespace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Editor with the text and the cursor at the `|`
+    fn editor(text: &str) -> LineEditor {
+        let mut editor = LineEditor::from(text.replace('|', "").as_str());
+        editor.cursor = text.find('|').unwrap();
+        editor
+    }
+
+    /// The text with a `|` at the cursor
+    fn cursor(editor: &LineEditor) -> String {
+        let mut text = editor.text.to_owned();
+        text.insert(editor.cursor, '|');
+        text
+    }
+
+    /// A key press without modifiers
+    fn press(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn edits_at_the_cursor() {
+        let mut e = editor("héllo| wörld");
+        e.insert('!');
+        assert_eq!(cursor(&e), "héllo!| wörld");
+        e.left();
+        e.left();
+        e.backspace();
+        assert_eq!(cursor(&e), "hél|o! wörld");
+        e.delete();
+        assert_eq!(cursor(&e), "hél|! wörld");
+        e.end();
+        e.backspace();
+        e.home();
+        e.delete();
+        assert_eq!(cursor(&e), "|él! wörl");
+    }
+
+    #[test]
+    fn cursor_moves_over_graphemes() {
+        let mut e = LineEditor::from("ae\u{301}");
+        e.left();
+        assert_eq!(cursor(&e), "a|e\u{301}");
+        e.right();
+        e.backspace();
+        assert_eq!(e.value(), "a");
+        e.insert('e');
+        e.insert('\u{301}');
+        assert_eq!(cursor(&e), "ae\u{301}|");
+    }
+
+    #[test]
+    fn words() {
+        let mut e = editor("one  two thr|ee");
+        e.word_left();
+        assert_eq!(cursor(&e), "one  two |three");
+        e.word_left();
+        e.word_left();
+        assert_eq!(cursor(&e), "|one  two three");
+        e.word_right();
+        assert_eq!(cursor(&e), "one|  two three");
+        e.word_right();
+        e.handle(KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL));
+        assert_eq!(cursor(&e), "one  | three");
+        e.handle(KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL));
+        assert_eq!(cursor(&e), "| three");
+    }
+
+    #[test]
+    fn delete_to_start_and_end() {
+        let mut e = editor("one |two");
+        e.handle(KeyEvent::new(KeyCode::Char('k'), KeyModifiers::CONTROL));
+        assert_eq!(cursor(&e), "one |");
+        let mut e = editor("one |two");
+        e.handle(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL));
+        assert_eq!(cursor(&e), "|two");
+        assert!(!e.handle(press(KeyCode::Enter)));
+    }
+
+    #[test]
+    fn pasted_text_stays_on_one_line() {
+        let mut e = LineEditor::default();
+        e.insert_str("one\r\ntwo\tthree\u{7}");
+        assert_eq!(cursor(&e), "one two three|");
+    }
+
+    #[test]
+    fn completes_ignoring_case() {
+        let candidates = vec![String::from("home"), String::from("Work")];
+        let mut e = LineEditor::from("wo");
+        assert_eq!(e.completion_suffix(&candidates), "rk");
+        assert!(e.complete(&candidates));
+        assert_eq!(cursor(&e), "Work|");
+        assert!(!e.complete(&candidates));
+        assert!(!LineEditor::default().complete(&candidates));
+    }
+
+    #[test]
+    fn view_scrolls_to_the_cursor() {
+        let mut e = LineEditor::from("abcdefgh");
+        assert_eq!(e.view(4), (String::from("fgh"), 3));
+        e.home();
+        assert_eq!(e.view(4), (String::from("abcd"), 0));
+        e.right();
+        e.right();
+        // the text only scrolls when the cursor would leave the view
+        assert_eq!(e.view(4), (String::from("abcd"), 2));
+    }
+
+    #[test]
+    fn view_uses_the_width_of_characters() {
+        let mut e = LineEditor::from("日本語");
+        assert_eq!(e.view(10), (String::from("日本語"), 6));
+        e.scroll = 1;
+        e.home();
+        e.right();
+        assert_eq!(e.view(4), (String::from(" 本"), 1));
+    }
+}
@@ -0,0 +1,437 @@
+use backend::error::Error;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// Something the user can do with a key, what it does depends on the view that is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Back,
+    Next,
+    Previous,
+    Left,
+    Right,
+    SwapDown,
+    SwapUp,
+    MoveLeft,
+    MoveRight,
+    Delete,
+    New,
+    CycleState,
+    Edit,
+    EditInEditor,
+    Prioritize,
+    Deprioritize,
+    Search,
+    FilterTags,
+    ToggleTagMatch,
+    FilterState,
+    FilterCategory,
+    Sort,
+    SortDirection,
+    Group,
+    Board,
+    Detail,
+    NextNotebook,
+    OpenNotebook,
+    Indent,
+    Outdent,
+    Checklist,
+    Categories,
+    ToggleItem,
+    Archive,
+    ShowArchive,
+    Undo,
+    Redo,
+    Submit,
+    Cancel,
+    NextField,
+    PreviousField,
+    NextChoice,
+    PreviousChoice,
+}
+
+impl Action {
+    /// All the actions with their default keys
+    const DEFAULTS: [(Action, &'static [&'static str]); 44] = [
+        (Action::Quit, &["q"]),
+        (Action::Back, &["esc"]),
+        (Action::Next, &["j", "down"]),
+        (Action::Previous, &["k", "up"]),
+        (Action::Left, &["h", "left"]),
+        (Action::Right, &["l", "right"]),
+        (Action::SwapDown, &["J"]),
+        (Action::SwapUp, &["K"]),
+        (Action::MoveLeft, &["H"]),
+        (Action::MoveRight, &["L"]),
+        (Action::Delete, &["d"]),
+        (Action::New, &["n"]),
+        (Action::CycleState, &["s"]),
+        (Action::Edit, &["e"]),
+        (Action::EditInEditor, &["E"]),
+        (Action::Prioritize, &["p"]),
+        (Action::Deprioritize, &["P"]),
+        (Action::Search, &["/"]),
+        (Action::FilterTags, &["t"]),
+        (Action::ToggleTagMatch, &["T"]),
+        (Action::FilterState, &["f"]),
+        (Action::FilterCategory, &["C"]),
+        (Action::Sort, &["o"]),
+        (Action::SortDirection, &["O"]),
+        (Action::Group, &["g"]),
+        (Action::Board, &["B"]),
+        (Action::Detail, &["enter"]),
+        (Action::NextNotebook, &["tab"]),
+        (Action::OpenNotebook, &["b"]),
+        (Action::Indent, &[">"]),
+        (Action::Outdent, &["<"]),
+        (Action::Checklist, &["c"]),
+        (Action::Categories, &["m"]),
+        (Action::ToggleItem, &["space", "x"]),
+        (Action::Archive, &["a"]),
+        (Action::ShowArchive, &["A"]),
+        (Action::Undo, &["u"]),
+        (Action::Redo, &["ctrl-r"]),
+        (Action::Submit, &["enter"]),
+        (Action::Cancel, &["esc"]),
+        (Action::NextField, &["tab", "down"]),
+        (Action::PreviousField, &["backtab", "up"]),
+        (Action::NextChoice, &["right", "space"]),
+        (Action::PreviousChoice, &["left"]),
+    ];
+
+    /// Name of the action in the configuration file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Back => "back",
+            Action::Next => "next",
+            Action::Previous => "previous",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::SwapDown => "swap_down",
+            Action::SwapUp => "swap_up",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::Delete => "delete",
+            Action::New => "new",
+            Action::CycleState => "cycle_state",
+            Action::Edit => "edit",
+            Action::EditInEditor => "edit_in_editor",
+            Action::Prioritize => "prioritize",
+            Action::Deprioritize => "deprioritize",
+            Action::Search => "search",
+            Action::FilterTags => "filter_tags",
+            Action::ToggleTagMatch => "toggle_tag_match",
+            Action::FilterState => "filter_state",
+            Action::FilterCategory => "filter_category",
+            Action::Sort => "sort",
+            Action::SortDirection => "sort_direction",
+            Action::Group => "group",
+            Action::Board => "board",
+            Action::Detail => "detail",
+            Action::NextNotebook => "next_notebook",
+            Action::OpenNotebook => "open_notebook",
+            Action::Indent => "indent",
+            Action::Outdent => "outdent",
+            Action::Checklist => "checklist",
+            Action::Categories => "categories",
+            Action::ToggleItem => "toggle_item",
+            Action::Archive => "archive",
+            Action::ShowArchive => "show_archive",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Submit => "submit",
+            Action::Cancel => "cancel",
+            Action::NextField => "next_field",
+            Action::PreviousField => "previous_field",
+            Action::NextChoice => "next_choice",
+            Action::PreviousChoice => "previous_choice",
+        }
+    }
+
+    /// Whether the action is done while typing, in the note form and the other inputs
+    /// These keys are bound separately from the keys of the views, as they are never active at
+    /// the same time
+    fn is_input(&self) -> bool {
+        matches!(
+            self,
+            Action::Submit
+                | Action::Cancel
+                | Action::NextField
+                | Action::PreviousField
+                | Action::NextChoice
+                | Action::PreviousChoice
+        )
+    }
+
+    /// The action with a name from the configuration file
+    fn from_name(name: &str) -> Option<Action> {
+        Action::DEFAULTS
+            .iter()
+            .map(|(action, _)| *action)
+            .find(|action| action.name() == name)
+    }
+}
+
+/// A key with the modifiers that are held down
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// Key of a key press, where shift is part of the character
+    pub fn from_event(event: KeyEvent) -> Self {
+        Self {
+            code: event.code,
+            modifiers: event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT),
+        }
+    }
+
+    /// Parse a key like `j`, `J`, `enter`, `space` or `ctrl-r`
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        loop {
+            let lower = rest.to_lowercase();
+            if lower.starts_with("ctrl-") && rest.len() > 5 {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = &rest[5..];
+            } else if lower.starts_with("alt-") && rest.len() > 4 {
+                modifiers |= KeyModifiers::ALT;
+                rest = &rest[4..];
+            } else {
+                break;
+            }
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "space" => KeyCode::Char(' '),
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                f => KeyCode::F(f.strip_prefix('f')?.parse().ok()?),
+            },
+        };
+        Some(Self { code, modifiers })
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            KeyCode::Enter => write!(f, "enter"),
+            KeyCode::Esc => write!(f, "esc"),
+            KeyCode::Tab => write!(f, "tab"),
+            KeyCode::BackTab => write!(f, "backtab"),
+            KeyCode::Backspace => write!(f, "backspace"),
+            KeyCode::Delete => write!(f, "delete"),
+            KeyCode::Insert => write!(f, "insert"),
+            KeyCode::Home => write!(f, "home"),
+            KeyCode::End => write!(f, "end"),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            KeyCode::Up => write!(f, "up"),
+            KeyCode::Down => write!(f, "down"),
+            KeyCode::Left => write!(f, "left"),
+            KeyCode::Right => write!(f, "right"),
+            _ => write!(f, "?"),
+        }
+    }
+}
+
+/// Which action every key does
+pub struct Keymap {
+    /// The action of every key that is bound in the views
+    actions: HashMap<Key, Action>,
+
+    /// The action of every key that is bound while typing
+    input_actions: HashMap<Key, Action>,
+
+    /// The keys of every action, in the order they were configured
+    keys: HashMap<Action, Vec<Key>>,
+}
+
+impl Keymap {
+    /// The default keys, where the keys of the configured actions are replaced
+    /// A key that is bound to more than one action is an error
+    pub fn new(overrides: &HashMap<String, Vec<String>>) -> Result<Self, Error> {
+        let invalid = |msg: String| Error::InvalidConfig(format!("[keys]: {}", msg));
+
+        let mut keys: HashMap<Action, Vec<Key>> = HashMap::new();
+        for (action, defaults) in Action::DEFAULTS {
+            let parsed = defaults.iter().filter_map(|k| Key::parse(k)).collect();
+            keys.insert(action, parsed);
+        }
+        for (name, configured) in overrides {
+            let action = Action::from_name(name)
+                .ok_or_else(|| invalid(format!("unknown action {}", name)))?;
+            let parsed = configured
+                .iter()
+                .map(|k| Key::parse(k).ok_or_else(|| invalid(format!("invalid key {}", k))))
+                .collect::<Result<Vec<Key>, Error>>()?;
+            keys.insert(action, parsed);
+        }
+
+        let mut actions: HashMap<Key, Action> = HashMap::new();
+        let mut input_actions: HashMap<Key, Action> = HashMap::new();
+        // check in a fixed order, so the same conflict is reported every time
+        for (action, _) in Action::DEFAULTS {
+            let bound = match action.is_input() {
+                true => &mut input_actions,
+                false => &mut actions,
+            };
+            for key in &keys[&action] {
+                if let Some(other) = bound.insert(*key, action) {
+                    return Err(invalid(format!(
+                        "{} is bound to both {} and {}",
+                        key,
+                        other.name(),
+                        action.name()
+                    )));
+                }
+            }
+        }
+        Ok(Self {
+            actions,
+            input_actions,
+            keys,
+        })
+    }
+
+    /// Action of a key press in a view, if the key is bound
+    pub fn action(&self, event: KeyEvent) -> Option<Action> {
+        self.actions.get(&Key::from_event(event)).copied()
+    }
+
+    /// Action of a key press while typing, if the key is bound
+    pub fn input_action(&self, event: KeyEvent) -> Option<Action> {
+        self.input_actions.get(&Key::from_event(event)).copied()
+    }
+
+    /// First key that is bound to the action
+    pub fn key(&self, action: Action) -> Option<Key> {
+        self.keys.get(&action)?.first().copied()
+    }
+
+    /// Help for the actions, like `(j|down) next | (k|up) previous`
+    /// Actions without keys are left out
+    pub fn help(&self, actions: &[(Action, &str)]) -> String {
+        actions
+            .iter()
+            .filter_map(|(action, description)| {
+                let keys = self.keys.get(action).filter(|keys| !keys.is_empty())?;
+                let keys: Vec<String> = keys.iter().map(Key::to_string).collect();
+                Some(format!("({}) {}", keys.join("|"), description))
+            })
+            .collect::<Vec<String>>()
+            .join(" | ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Configured keys for a single action
+    fn overrides(action: &str, keys: &[&str]) -> HashMap<String, Vec<String>> {
+        HashMap::from([(
+            action.to_owned(),
+            keys.iter().map(|k| k.to_string()).collect(),
+        )])
+    }
+
+    /// A key press without modifiers, other than shift
+    fn press(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::SHIFT)
+    }
+
+    #[test]
+    fn parse_and_display_keys() {
+        for key in [
+            "j", "J", "enter", "space", "ctrl-r", "alt-left", "f5", "-", "<",
+        ] {
+            assert_eq!(Key::parse(key).unwrap().to_string(), key);
+        }
+        assert_eq!(Key::parse("Ctrl-R").unwrap().to_string(), "ctrl-R");
+        assert!(Key::parse("").is_none());
+        assert!(Key::parse("nope").is_none());
+    }
+
+    #[test]
+    fn default_keymap_has_no_conflicts() {
+        let keymap = Keymap::new(&HashMap::new()).unwrap();
+        assert_eq!(
+            keymap.action(press(KeyCode::Char('J'))),
+            Some(Action::SwapDown)
+        );
+        let redo = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL);
+        assert_eq!(keymap.action(redo), Some(Action::Redo));
+        assert_eq!(keymap.action(press(KeyCode::Char('r'))), None);
+    }
+
+    #[test]
+    fn overrides_replace_the_default_keys() {
+        let keymap = Keymap::new(&overrides("next", &["ctrl-n"])).unwrap();
+        assert_eq!(keymap.action(press(KeyCode::Char('j'))), None);
+        let next = KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL);
+        assert_eq!(keymap.action(next), Some(Action::Next));
+        assert_eq!(keymap.help(&[(Action::Next, "down")]), "(ctrl-n) down");
+    }
+
+    #[test]
+    fn conflicts_and_unknown_names_are_errors() {
+        let e = Keymap::new(&overrides("next", &["n"])).err().unwrap();
+        assert!(e.to_string().contains("n is bound to both next and new"));
+        assert!(Keymap::new(&overrides("fly", &["z"])).is_err());
+        assert!(Keymap::new(&overrides("next", &["nope"])).is_err());
+    }
+
+    #[test]
+    fn input_keys_are_bound_separately() {
+        let keymap = Keymap::new(&HashMap::new()).unwrap();
+        let tab = press(KeyCode::Tab);
+        assert_eq!(keymap.action(tab), Some(Action::NextNotebook));
+        assert_eq!(keymap.input_action(tab), Some(Action::NextField));
+        assert_eq!(keymap.input_action(press(KeyCode::Char('j'))), None);
+
+        let e = Keymap::new(&overrides("submit", &["tab"])).err().unwrap();
+        assert!(e
+            .to_string()
+            .contains("tab is bound to both submit and next_field"));
+    }
+
+    #[test]
+    fn unbound_actions_are_left_out_of_the_help() {
+        let keymap = Keymap::new(&overrides("undo", &[])).unwrap();
+        let help = keymap.help(&[(Action::Undo, "undo"), (Action::Quit, "quit")]);
+        assert_eq!(help, "(q) quit");
+    }
+}
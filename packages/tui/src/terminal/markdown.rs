@@ -0,0 +1,243 @@
+use tui::{
+    style::{Modifier, Style},
+    text::{Span, Spans},
+};
+
+/// Render markdown into styled lines
+/// Supports headings, (ordered) lists, code blocks, code spans and emphasis
+pub fn render(text: &str, code: Style) -> Vec<Spans<'static>> {
+    let mut lines = Vec::new();
+    let mut in_code_block = false;
+
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+
+        if in_code_block {
+            lines.push(Spans::from(Span::styled(line.to_owned(), code)));
+            continue;
+        }
+
+        lines.push(render_line(line, code));
+    }
+
+    lines
+}
+
+/// Render a single line of markdown outside of a code block
+fn render_line(line: &str, code: Style) -> Spans<'static> {
+    let trimmed = line.trim_start();
+    let indent = &line[..line.len() - trimmed.len()];
+
+    // headings
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+        let style = match level {
+            1 => Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            _ => Style::default().add_modifier(Modifier::BOLD),
+        };
+        return Spans::from(inline(trimmed[level..].trim(), style, code));
+    }
+
+    // unordered lists
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(item) = trimmed.strip_prefix(bullet) {
+            let mut spans = vec![Span::raw(format!("{}• ", indent))];
+            spans.extend(inline(item, Style::default(), code));
+            return Spans::from(spans);
+        }
+    }
+
+    // ordered lists
+    let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 && trimmed[digits..].starts_with(". ") {
+        let mut spans = vec![Span::styled(
+            format!("{}{}. ", indent, &trimmed[..digits]),
+            Style::default().add_modifier(Modifier::BOLD),
+        )];
+        spans.extend(inline(&trimmed[digits + 2..], Style::default(), code));
+        return Spans::from(spans);
+    }
+
+    Spans::from(inline(line, Style::default(), code))
+}
+
+/// Render code spans and emphasis within a line
+/// Markers without a closing counterpart are shown as is
+fn inline(text: &str, base: Style, code_style: Style) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut buf = String::new();
+    let mut bold = false;
+    let mut italic = false;
+    let mut i = 0;
+
+    let style = |bold: bool, italic: bool| {
+        let mut style = base;
+        if bold {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        if italic {
+            style = style.add_modifier(Modifier::ITALIC);
+        }
+        style
+    };
+
+    while i < text.len() {
+        let rest = &text[i..];
+
+        if let Some(code) = rest.strip_prefix('`') {
+            if let Some(end) = code.find('`') {
+                flush(&mut spans, &mut buf, style(bold, italic));
+                spans.push(Span::styled(code[..end].to_owned(), code_style));
+                i += end + 2;
+                continue;
+            }
+        }
+
+        let marker = ["**", "__", "*", "_"]
+            .into_iter()
+            .find(|m| rest.starts_with(m));
+        if let Some(marker) = marker {
+            let active = match marker.len() {
+                2 => bold,
+                _ => italic,
+            };
+            // `_` within a word, like in `snake_case`, is not emphasis
+            let in_word = marker.starts_with('_')
+                && text[..i].chars().last().is_some_and(char::is_alphanumeric);
+            if active || (!in_word && rest[marker.len()..].contains(marker)) {
+                flush(&mut spans, &mut buf, style(bold, italic));
+                match marker.len() {
+                    2 => bold = !bold,
+                    _ => italic = !italic,
+                }
+                i += marker.len();
+                continue;
+            }
+        }
+
+        let c = rest.chars().next().unwrap_or_default();
+        buf.push(c);
+        i += c.len_utf8();
+    }
+    flush(&mut spans, &mut buf, style(bold, italic));
+
+    spans
+}
+
+/// Move the buffered text into a span with the style
+fn flush(spans: &mut Vec<Span<'static>>, buf: &mut String, style: Style) {
+    if !buf.is_empty() {
+        spans.push(Span::styled(std::mem::take(buf), style));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Style of code in the tests
+    const CODE: Style = Style {
+        fg: Some(tui::style::Color::Yellow),
+        bg: None,
+        add_modifier: Modifier::empty(),
+        sub_modifier: Modifier::empty(),
+    };
+
+    /// The text and style of every span of a line
+    fn spans(line: &Spans) -> Vec<(String, Style)> {
+        line.0
+            .iter()
+            .map(|span| (span.content.to_string(), span.style))
+            .collect()
+    }
+
+    /// Style with the modifiers
+    fn with(modifier: Modifier) -> Style {
+        Style::default().add_modifier(modifier)
+    }
+
+    #[test]
+    fn headings_are_bold() {
+        let lines = render("# Title\n### Sub\n#hashtag", CODE);
+        assert_eq!(
+            spans(&lines[0]),
+            [(
+                String::from("Title"),
+                with(Modifier::BOLD | Modifier::UNDERLINED)
+            )]
+        );
+        assert_eq!(
+            spans(&lines[1]),
+            [(String::from("Sub"), with(Modifier::BOLD))]
+        );
+        // without a space it is not a heading
+        assert_eq!(
+            spans(&lines[2]),
+            [(String::from("#hashtag"), Style::default())]
+        );
+    }
+
+    #[test]
+    fn nested_lists_keep_their_indent() {
+        let lines = render("- one\n  * two\n    1. three", CODE);
+        assert_eq!(lines[0].0[0].content, "• ");
+        assert_eq!(lines[1].0[0].content, "  • ");
+        assert_eq!(lines[1].0[1].content, "two");
+        assert_eq!(lines[2].0[0].content, "    1. ");
+        assert_eq!(lines[2].0[1].content, "three");
+    }
+
+    #[test]
+    fn fenced_code_is_not_parsed() {
+        let lines = render("```rust\n# not a heading\n*a*\n```\n*b*", CODE);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(spans(&lines[0]), [(String::from("# not a heading"), CODE)]);
+        assert_eq!(spans(&lines[1]), [(String::from("*a*"), CODE)]);
+        assert_eq!(
+            spans(&lines[2]),
+            [(String::from("b"), with(Modifier::ITALIC))]
+        );
+    }
+
+    #[test]
+    fn code_spans_keep_emphasis_markers() {
+        let line = render_line("run `a * b` *now*", CODE);
+        assert_eq!(
+            spans(&line),
+            [
+                (String::from("run "), Style::default()),
+                (String::from("a * b"), CODE),
+                (String::from(" "), Style::default()),
+                (String::from("now"), with(Modifier::ITALIC)),
+            ]
+        );
+    }
+
+    #[test]
+    fn emphasis() {
+        let line = render_line("**bold** _italic_ __also bold__ *unclosed", CODE);
+        assert_eq!(
+            spans(&line),
+            [
+                (String::from("bold"), with(Modifier::BOLD)),
+                (String::from(" "), Style::default()),
+                (String::from("italic"), with(Modifier::ITALIC)),
+                (String::from(" "), Style::default()),
+                (String::from("also bold"), with(Modifier::BOLD)),
+                (String::from(" *unclosed"), Style::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn underscores_within_words_are_not_emphasis() {
+        let line = render_line("call snake_case_words now", CODE);
+        assert_eq!(
+            spans(&line),
+            [(String::from("call snake_case_words now"), Style::default())]
+        );
+    }
+}
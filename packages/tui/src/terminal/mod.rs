@@ -1,2 +1,8 @@
 pub mod app;
+pub mod editor;
+pub mod form;
 pub mod frontend;
+pub mod input;
+pub mod keymap;
+pub mod markdown;
+pub mod theme;
@@ -0,0 +1,383 @@
+use backend::config::Config;
+use backend::error::Error;
+use backend::note::State;
+use std::collections::HashMap;
+use std::env;
+use tui::style::{Color, Modifier, Style};
+
+/// Environment variable that turns off colors when it is set, see <https://no-color.org>
+pub const NO_COLOR_ENV: &str = "NO_COLOR";
+
+/// Names of the built-in themes
+pub const BUILTIN_THEMES: [&str; 4] = ["dark", "light", "high-contrast", "monochrome"];
+
+/// Styles of the parts of the TUI
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    /// Selected row, card or item
+    pub selected: Style,
+
+    /// Selected note that is about to be deleted
+    pub delete: Style,
+
+    /// Text that is typed in
+    pub input: Style,
+
+    /// Characters that match the search
+    pub matched: Style,
+
+    /// Table header, labels and category groups
+    pub header: Style,
+
+    /// Less important text, like hints and the columns of the board that are not selected
+    pub dim: Style,
+
+    /// Code in descriptions
+    pub code: Style,
+
+    /// Border of warnings
+    pub warning: Style,
+
+    /// Border of errors
+    pub error: Style,
+
+    /// Notes without a state
+    pub none: Style,
+
+    /// Notes to do
+    pub todo: Style,
+
+    /// Notes in progress
+    pub in_progress: Style,
+
+    /// Notes that are done
+    pub done: Style,
+
+    /// Notes past their due date
+    pub expired: Style,
+
+    /// Styles of the categories, others have the default style
+    pub categories: HashMap<String, Style>,
+}
+
+impl Theme {
+    /// The configured theme
+    /// Without one it is dark, or monochrome when `NO_COLOR` is set
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        let no_color = env::var(NO_COLOR_ENV).is_ok_and(|v| !v.is_empty());
+        Theme::select(config, no_color)
+    }
+
+    /// The configured theme, where the default depends on whether colors are turned off
+    fn select(config: &Config, no_color: bool) -> Result<Self, Error> {
+        let name = match &config.theme.name {
+            Some(name) => name.as_str(),
+            None if no_color => "monochrome",
+            None => "dark",
+        };
+        let mut theme = Theme::named(config, name)?;
+        for (category, value) in &config.theme.categories {
+            let style = parse_style(value).ok_or_else(|| {
+                Error::InvalidConfig(format!(
+                    "[theme.categories]: invalid style {} of {}",
+                    value, category
+                ))
+            })?;
+            theme.categories.insert(category.to_owned(), style);
+        }
+        Ok(theme)
+    }
+
+    /// The built-in theme or theme of the user with the name
+    fn named(config: &Config, name: &str) -> Result<Self, Error> {
+        // a theme of the user can also change a built-in theme with the same name
+        let user = match config.themes.get(name) {
+            Some(user) => user,
+            None => {
+                return Theme::builtin(name).ok_or_else(|| {
+                    Error::InvalidConfig(format!(
+                        "[theme]: unknown theme {}, expected one of {} or a [themes.{}] section",
+                        name,
+                        BUILTIN_THEMES.join(", "),
+                        name
+                    ))
+                })
+            }
+        };
+        let invalid = |msg: String| Error::InvalidConfig(format!("[themes.{}]: {}", name, msg));
+        let base = user.base.as_deref().unwrap_or("dark");
+        let mut theme =
+            Theme::builtin(base).ok_or_else(|| invalid(format!("unknown base theme {}", base)))?;
+
+        let mut styles: Vec<_> = user.styles.iter().collect();
+        styles.sort();
+        for (part, value) in styles {
+            let style =
+                parse_style(value).ok_or_else(|| invalid(format!("invalid style {}", value)))?;
+            *theme
+                .style_mut(part)
+                .ok_or_else(|| invalid(format!("unknown part {}", part)))? = style;
+        }
+        Ok(theme)
+    }
+
+    /// The built-in theme with the name
+    pub fn builtin(name: &str) -> Option<Self> {
+        let fg = |color| Style::default().fg(color);
+        let bold = |color| fg(color).add_modifier(Modifier::BOLD);
+        let modifier = |modifier| Style::default().add_modifier(modifier);
+
+        let theme = match name {
+            "dark" => Self {
+                selected: modifier(Modifier::REVERSED),
+                delete: modifier(Modifier::REVERSED).fg(Color::Red),
+                input: fg(Color::White),
+                matched: bold(Color::Yellow),
+                header: modifier(Modifier::BOLD),
+                dim: modifier(Modifier::DIM),
+                code: fg(Color::Yellow),
+                warning: fg(Color::Yellow),
+                error: fg(Color::Red),
+                none: Style::default(),
+                todo: fg(Color::LightBlue),
+                in_progress: fg(Color::Yellow),
+                done: fg(Color::Green),
+                expired: fg(Color::Red),
+                categories: HashMap::new(),
+            },
+            "light" => Self {
+                selected: modifier(Modifier::REVERSED),
+                delete: modifier(Modifier::REVERSED).fg(Color::Red),
+                input: fg(Color::Black),
+                matched: bold(Color::Magenta),
+                header: modifier(Modifier::BOLD),
+                dim: fg(Color::DarkGray),
+                code: fg(Color::Magenta),
+                warning: fg(Color::Indexed(130)),
+                error: fg(Color::Red),
+                none: Style::default(),
+                todo: fg(Color::Blue),
+                in_progress: fg(Color::Indexed(130)),
+                done: fg(Color::Green),
+                expired: fg(Color::Red),
+                categories: HashMap::new(),
+            },
+            "high-contrast" => Self {
+                selected: bold(Color::Black).bg(Color::Yellow),
+                delete: bold(Color::White).bg(Color::Red),
+                input: bold(Color::White),
+                matched: fg(Color::Black).bg(Color::Yellow),
+                header: modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                dim: Style::default(),
+                code: fg(Color::LightCyan),
+                warning: bold(Color::LightYellow),
+                error: bold(Color::LightRed),
+                none: bold(Color::White),
+                todo: bold(Color::LightCyan),
+                in_progress: bold(Color::LightYellow),
+                done: bold(Color::LightGreen),
+                expired: bold(Color::LightRed),
+                categories: HashMap::new(),
+            },
+            "monochrome" => Self {
+                selected: modifier(Modifier::REVERSED),
+                delete: modifier(Modifier::REVERSED | Modifier::CROSSED_OUT),
+                input: Style::default(),
+                matched: modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                header: modifier(Modifier::BOLD),
+                dim: modifier(Modifier::DIM),
+                code: modifier(Modifier::ITALIC),
+                warning: modifier(Modifier::BOLD),
+                error: modifier(Modifier::BOLD),
+                none: Style::default(),
+                todo: Style::default(),
+                in_progress: modifier(Modifier::BOLD),
+                done: modifier(Modifier::DIM),
+                expired: modifier(Modifier::UNDERLINED),
+                categories: HashMap::new(),
+            },
+            _ => return None,
+        };
+        Some(theme)
+    }
+
+    /// Style of the notes with the state
+    pub fn state(&self, state: &State) -> Style {
+        match state {
+            State::None => self.none,
+            State::Todo => self.todo,
+            State::InProgress => self.in_progress,
+            State::Done => self.done,
+            State::Expired => self.expired,
+        }
+    }
+
+    /// Style of the category
+    pub fn category(&self, category: &str) -> Style {
+        self.categories.get(category).copied().unwrap_or_default()
+    }
+
+    /// Style of a part by its name in the configuration
+    fn style_mut(&mut self, part: &str) -> Option<&mut Style> {
+        let style = match part {
+            "selected" => &mut self.selected,
+            "delete" => &mut self.delete,
+            "input" => &mut self.input,
+            "match" => &mut self.matched,
+            "header" => &mut self.header,
+            "dim" => &mut self.dim,
+            "code" => &mut self.code,
+            "warning" => &mut self.warning,
+            "error" => &mut self.error,
+            "none" => &mut self.none,
+            "todo" => &mut self.todo,
+            "in_progress" => &mut self.in_progress,
+            "done" => &mut self.done,
+            "expired" => &mut self.expired,
+            _ => return None,
+        };
+        Some(style)
+    }
+}
+
+/// Parse a style like `red`, `bold white on blue`, `#ff8800` or `italic 208`
+/// The color before `on` is the foreground and the one after it the background
+fn parse_style(s: &str) -> Option<Style> {
+    let mut style = Style::default();
+    let mut background = false;
+    for word in s.split_whitespace() {
+        let word = word.to_lowercase();
+        let modifier = match word.as_str() {
+            "bold" => Some(Modifier::BOLD),
+            "dim" => Some(Modifier::DIM),
+            "italic" => Some(Modifier::ITALIC),
+            "underlined" => Some(Modifier::UNDERLINED),
+            "reversed" => Some(Modifier::REVERSED),
+            "crossed_out" => Some(Modifier::CROSSED_OUT),
+            _ => None,
+        };
+        match (modifier, word.as_str()) {
+            (Some(modifier), _) => style = style.add_modifier(modifier),
+            (None, "on") => background = true,
+            (None, color) if background => style = style.bg(parse_color(color)?),
+            (None, color) => style = style.fg(parse_color(color)?),
+        }
+    }
+    Some(style)
+}
+
+/// Parse a color name, a `#rrggbb` color or a number of the 256 terminal colors
+fn parse_color(s: &str) -> Option<Color> {
+    let color = match s.replace(['_', '-'], "").as_str() {
+        "reset" | "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        hex if hex.starts_with('#') && hex.len() == 7 => {
+            let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+            Color::Rgb(channel(1)?, channel(3)?, channel(5)?)
+        }
+        n => Color::Indexed(n.parse().ok()?),
+    };
+    Some(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::config::UserTheme;
+
+    /// Configuration with the theme
+    fn config(name: Option<&str>) -> Config {
+        let mut config = Config::default();
+        config.theme.name = name.map(str::to_owned);
+        config
+    }
+
+    /// Configuration with a theme of the user, which is also the configured theme
+    fn user_config(base: &str, styles: &[(&str, &str)]) -> Config {
+        let mut config = config(Some("mine"));
+        let styles = styles
+            .iter()
+            .map(|(part, style)| (part.to_string(), style.to_string()))
+            .collect();
+        let theme = UserTheme {
+            base: Some(base.to_owned()),
+            styles,
+        };
+        config.themes.insert(String::from("mine"), theme);
+        config
+    }
+
+    #[test]
+    fn builtin_themes_exist() {
+        for name in BUILTIN_THEMES {
+            assert!(Theme::builtin(name).is_some());
+        }
+        assert!(Theme::builtin("sepia").is_none());
+    }
+
+    #[test]
+    fn no_color_defaults_to_monochrome() {
+        let theme = Theme::select(&config(None), true).unwrap();
+        assert_eq!(theme, Theme::builtin("monochrome").unwrap());
+        // a configured theme wins from NO_COLOR
+        let theme = Theme::select(&config(Some("light")), true).unwrap();
+        assert_eq!(theme, Theme::builtin("light").unwrap());
+    }
+
+    #[test]
+    fn user_theme_changes_its_base() {
+        let config = user_config("light", &[("todo", "bold #ff8800 on 17")]);
+        let theme = Theme::select(&config, false).unwrap();
+        let light = Theme::builtin("light").unwrap();
+        assert_eq!(
+            theme.todo,
+            Style::default()
+                .fg(Color::Rgb(255, 136, 0))
+                .bg(Color::Indexed(17))
+                .add_modifier(Modifier::BOLD)
+        );
+        assert_eq!(theme.done, light.done);
+    }
+
+    #[test]
+    fn categories_have_their_own_style() {
+        let mut config = config(Some("monochrome"));
+        config
+            .theme
+            .categories
+            .insert(String::from("work"), String::from("blue"));
+        let theme = Theme::select(&config, false).unwrap();
+        assert_eq!(theme.category("work"), Style::default().fg(Color::Blue));
+        assert_eq!(theme.category("home"), Style::default());
+        config
+            .theme
+            .categories
+            .insert(String::from("home"), String::from("blurple"));
+        assert!(Theme::select(&config, false).is_err());
+    }
+
+    #[test]
+    fn invalid_themes_are_errors() {
+        assert!(Theme::select(&config(Some("sepia")), false).is_err());
+        let config = user_config("dark", &[("todo", "blurple")]);
+        assert!(Theme::select(&config, false).is_err());
+        let config = user_config("dark", &[("title", "red")]);
+        let e = Theme::select(&config, false).err().unwrap();
+        assert!(e.to_string().contains("unknown part title"));
+    }
+}